@@ -1,3 +1,5 @@
 fn main() {
     embuild::espidf::sysenv::output();
+    // The chip being built for is given as a cfg by esp-idf-sys.
+    println!("cargo:rustc-check-cfg=cfg(esp32, esp32s2, esp32s3, esp32c3, esp32c6)");
 }
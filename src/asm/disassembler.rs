@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use esp_idf_hal::gpio::Level;
+
+use super::parser::is_identifier;
+use crate::{
+    resource::{AccessMode, DriveCapability, Edge, PinConfig, PullMode, WaitMode},
+    task::{Condition, Operand, Register, Shot, Task, TaskPriority, TaskStep, TimeAnchor, Value},
+};
+
+/// Print a task out as text that [`assemble`](super::assemble) reads back into the same task.
+///
+/// Every step that is jumped to is given a label, numbered in the order that the
+/// labels appear. Durations are always written in milliseconds.
+pub fn disassemble(task: &Task) -> String {
+    let steps = task.steps();
+    let context = task.context();
+
+    let mut targets: Vec<_> = steps.iter().filter_map(TaskStep::jump_target).collect();
+    targets.sort();
+    targets.dedup();
+    let labels: HashMap<usize, String> = targets
+        .into_iter()
+        .enumerate()
+        .map(|(index, target)| (target, format!("L{index}")))
+        .collect();
+
+    let mut lines = vec![
+        format!(".name {}", quote(context.name())),
+        format!(".priority {}", priority(*context.priority())),
+        format!(".shots {}", shots(*task.shots())),
+    ];
+    for (directive, mode) in [
+        (".pins", AccessMode::Output),
+        (".inputs", AccessMode::Input),
+        (".shared", AccessMode::Shared),
+    ] {
+        let pins: Vec<_> = context
+            .pins_used()
+            .iter()
+            .filter(|pin| context.pin_mode(**pin) == mode)
+            .map(i32::to_string)
+            .collect();
+        if !pins.is_empty() {
+            lines.push(format!("{directive} {}", pins.join(", ")));
+        }
+    }
+    for (pin, config) in context.pin_configs() {
+        lines.push(format!(".config {pin}{}", pin_config(config)));
+    }
+    for (semaphore, permits) in context.max_claims() {
+        lines.push(format!(".claim {} {permits}", name(semaphore)));
+    }
+    lines.push(String::new());
+
+    // A jump may land just past the last step, so that index can have a label too.
+    for index in 0..=steps.len() {
+        if let Some(label) = labels.get(&index) {
+            lines.push(format!("{label}:"));
+        }
+        if let Some(step) = steps.get(index) {
+            lines.push(format!("    {}", instruction(step, &labels)));
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// The options of a pin configuration, each starting with a space, leaving out
+/// any that are as the chip resets them.
+fn pin_config(config: &PinConfig) -> String {
+    let mut options = String::new();
+    match config.pull() {
+        PullMode::Floating => (),
+        PullMode::Up => options.push_str(" pull up"),
+        PullMode::Down => options.push_str(" pull down"),
+        PullMode::UpDown => options.push_str(" pull up_down"),
+    }
+    if *config.open_drain() {
+        options.push_str(" open_drain");
+    }
+    if let Some(drive) = config.drive() {
+        options.push_str(match drive {
+            DriveCapability::I5mA => " drive 5mA",
+            DriveCapability::I10mA => " drive 10mA",
+            DriveCapability::I20mA => " drive 20mA",
+            DriveCapability::I40mA => " drive 40mA",
+        });
+    }
+    if let Some(value) = config.initial_level() {
+        options.push_str(&format!(" initial {}", level(*value)));
+    }
+    options
+}
+
+fn instruction(step: &TaskStep, labels: &HashMap<usize, String>) -> String {
+    match step {
+        TaskStep::ReadGPIO(pin, destination) => format!("read {pin} -> {}", register(*destination)),
+        TaskStep::WriteGPIO(pin, value) => format!("{} {pin}", level(*value)),
+        TaskStep::Yield(ms) => format!("wait {ms}ms"),
+        TaskStep::YieldNow => "yield".into(),
+        TaskStep::SleepUntil(ms, anchor) => format!(
+            "sleep {ms}ms after {}",
+            match anchor {
+                TimeAnchor::Start => "start",
+                TimeAnchor::Mark => "mark",
+            }
+        ),
+        TaskStep::MarkTime => "mark".into(),
+        TaskStep::WaitLevel(pin, value, ms, result) => format!(
+            "wait_level {pin} {}{}{}",
+            level(*value),
+            timeout(*ms),
+            destination(*result)
+        ),
+        TaskStep::WaitEdge(pin, edge, ms, result) => format!(
+            "wait_edge {pin} {}{}{}",
+            match edge {
+                Edge::Rising => "rising",
+                Edge::Falling => "falling",
+                Edge::Any => "any",
+            },
+            timeout(*ms),
+            destination(*result)
+        ),
+        TaskStep::Send(channel, value) => format!("send {} {value}", name(channel)),
+        TaskStep::Recv(channel, destination) => {
+            format!("recv {} -> {}", name(channel), register(*destination))
+        }
+        TaskStep::Store(variable, source) => {
+            format!("store {} -> {}", register(*source), name(variable))
+        }
+        TaskStep::Load(destination, variable) => {
+            format!("load {} -> {}", name(variable), register(*destination))
+        }
+        TaskStep::Set(destination, value) => {
+            format!("set {} {}", register(*destination), operand(*value))
+        }
+        TaskStep::Add(destination, value) => {
+            format!("add {} {}", register(*destination), operand(*value))
+        }
+        TaskStep::Sub(destination, value) => {
+            format!("sub {} {}", register(*destination), operand(*value))
+        }
+        TaskStep::Compare(result, source, value) => format!(
+            "cmp {} {} -> {}",
+            register(*source),
+            operand(*value),
+            register(*result)
+        ),
+        TaskStep::Jump(target) => format!("jump {}", labels[target]),
+        TaskStep::JumpIf(source, test, target) => format!(
+            "jump_if {} {} {}",
+            register(*source),
+            condition(*test),
+            labels[target]
+        ),
+        TaskStep::Loop(counter, target) => {
+            format!("loop {} {}", register(*counter), labels[target])
+        }
+        TaskStep::Call(routine) => format!("call {}", name(routine)),
+        TaskStep::Return => "ret".into(),
+        TaskStep::ReadGPIOIndirect(pin, destination) => {
+            format!("read {} -> {}", register(*pin), register(*destination))
+        }
+        TaskStep::WriteGPIOIndirect(pin, value) => {
+            format!("{} {}", level(*value), register(*pin))
+        }
+        TaskStep::YieldIndirect(ms) => format!("wait {}", register(*ms)),
+        TaskStep::Acquire(semaphore) => format!("acquire {}", name(semaphore)),
+        TaskStep::Release(semaphore) => format!("release {}", name(semaphore)),
+        TaskStep::SetBits(group, mask) => format!("set_bits {} {mask:#x}", name(group)),
+        TaskStep::ClearBits(group, mask) => {
+            format!("clear_bits {} {mask:#x}", name(group))
+        }
+        TaskStep::WaitBits(group, mask, mode, clear) => format!(
+            "wait_bits {} {mask:#x} {}{}",
+            name(group),
+            match mode {
+                WaitMode::All => "all",
+                WaitMode::Any => "any",
+            },
+            if *clear { " clear" } else { "" }
+        ),
+    }
+}
+
+/// A name as it is written, which is in quotes unless it is an identifier.
+fn name(name: &str) -> String {
+    match is_identifier(name) {
+        true => name.into(),
+        false => quote(name),
+    }
+}
+
+/// Put text in double quotes, escaping anything that would end the quotes or
+/// the line early.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn priority(priority: TaskPriority) -> String {
+    match priority {
+        TaskPriority::Low => "low".into(),
+        TaskPriority::Normal => "normal".into(),
+        TaskPriority::High => "high".into(),
+        TaskPriority::Custom(value) => value.to_string(),
+    }
+}
+
+fn shots(shots: Shot) -> String {
+    match shots {
+        Shot::Infinity => "infinity".into(),
+        Shot::Custom(count) => count.to_string(),
+    }
+}
+
+fn register(register: Register) -> String {
+    match register {
+        Register::Int(index) => format!("r{index}"),
+        Register::Bool(index) => format!("b{index}"),
+    }
+}
+
+fn operand(operand: Operand) -> String {
+    match operand {
+        Operand::Register(source) => register(source),
+        Operand::Immediate(Value::Int(value)) => value.to_string(),
+        Operand::Immediate(Value::Bool(value)) => value.to_string(),
+    }
+}
+
+fn level(level: Level) -> &'static str {
+    match level {
+        Level::High => "high",
+        Level::Low => "low",
+    }
+}
+
+fn condition(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Equal => "eq",
+        Condition::NotEqual => "ne",
+        Condition::Less => "lt",
+        Condition::LessOrEqual => "le",
+        Condition::Greater => "gt",
+        Condition::GreaterOrEqual => "ge",
+    }
+}
+
+fn timeout(timeout: Option<u32>) -> String {
+    timeout.map_or_else(String::new, |ms| format!(" timeout {ms}ms"))
+}
+
+fn destination(destination: Option<Register>) -> String {
+    destination.map_or_else(String::new, |result| format!(" -> {}", register(result)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::disassemble;
+    use crate::{
+        asm::assemble,
+        task::{
+            tests::{assert_same, described_task as task, every_step},
+            Task, TaskStep,
+        },
+        testing,
+    };
+
+    testing::tests!(
+        round_trips_every_step,
+        round_trips_quoted_names,
+        leaves_identifiers_unquoted,
+        rejects_bad_quotes
+    );
+
+    fn round_trip(task: &Task) -> anyhow::Result<()> {
+        let text = disassemble(task);
+        assert_same(&assemble(&text)?, task);
+        Ok(())
+    }
+
+    fn round_trips_every_step() -> anyhow::Result<()> {
+        round_trip(&task("Every Step", every_step("bus"))?)
+    }
+
+    fn round_trips_quoted_names() -> anyhow::Result<()> {
+        for name in [
+            "two words",
+            "\"quoted\"",
+            "back\\slash",
+            "a \\\" b",
+            "lines\nand\r\ttabs",
+            "; not a comment",
+            "label:",
+            "1st",
+            "",
+        ] {
+            round_trip(&task(name, every_step(name))?)?;
+        }
+        Ok(())
+    }
+
+    fn leaves_identifiers_unquoted() -> anyhow::Result<()> {
+        let text = disassemble(&task("Blink", vec![TaskStep::Call("blink_2".into())])?);
+        assert!(text.contains(".name \"Blink\"\n"), "{text}");
+        assert!(text.contains("call blink_2\n"), "{text}");
+        Ok(())
+    }
+
+    fn rejects_bad_quotes() -> anyhow::Result<()> {
+        for source in [
+            ".name \"Test\\\"\n",
+            ".name \"Test\\q\"\n",
+            ".name \"Test\"\ncall \"routine\n",
+        ] {
+            assert!(assemble(source).is_err(), "{source:?} should not assemble.");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+
+use getset::Getters;
+
+/// Represents a mistake in the source of a task, pointing at where it was made.
+///
+/// Lines and columns both count from one, matching what most editors show.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
+pub struct ParseError {
+    /// The line that the mistake is on.
+    #[getset(get = "pub")]
+    line: usize,
+    /// The column of the token that the mistake is in.
+    #[getset(get = "pub")]
+    column: usize,
+    /// A description of the mistake.
+    #[getset(get = "pub")]
+    message: String,
+}
+
+impl ParseError {
+    /// Create a new error at the given line and column.
+    pub fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,11 @@
+mod disassembler;
+mod error;
+mod parser;
+
+pub use disassembler::disassemble;
+pub use error::ParseError;
+pub use parser::assemble;
+
+/// The tests of every module in here.
+#[cfg(test)]
+pub const TESTS: &[&[crate::testing::Test]] = &[disassembler::tests::TESTS, parser::tests::TESTS];
@@ -0,0 +1,912 @@
+use std::collections::{BTreeMap, HashMap};
+
+use esp_idf_hal::gpio::Level;
+
+use super::ParseError;
+use crate::{
+    resource::{AccessMode, DriveCapability, Edge, PinConfig, PullMode, WaitMode},
+    task::{
+        Condition, Operand, Register, RegisterFile, Shot, Task, TaskContext, TaskPriority,
+        TaskStep, TimeAnchor, Value,
+    },
+};
+
+type Result<T> = std::result::Result<T, ParseError>;
+
+/// Assemble the text of a task into a task that can be scheduled.
+///
+/// Each line holds at most one instruction, such as `high 2`, `wait 1000ms` or
+/// `read 4 -> r0`, and anything after a `;` is a comment. Waiting on a pin may
+/// store the level it ends on in a register, as in
+/// `wait_level 3 high timeout 1s -> b0`. A line may start with a label such as
+/// `blink:`, which jumps refer to by name. Channels, variables,
+/// routines, semaphores and event groups are named by an identifier, or by any
+/// text in double quotes, where `\"`, `\\`, `\n`, `\r` and `\t` are escapes. The
+/// task itself is described with directives:
+///
+/// - `.name "Blink Blue LED"` is required, and is always in quotes.
+/// - `.priority low`, `normal`, `high` or a number, defaulting to low.
+/// - `.shots 5` or `.shots infinity`, defaulting to infinity as `task!` does.
+/// - `.pins 2, 4` assigns the pins that the task may use, as outputs. Each pin
+///   may only be declared once across `.pins`, `.inputs` and `.shared`.
+/// - `.inputs 3` assigns pins that the task only reads, which other tasks may read too.
+/// - `.shared 5` assigns pins that the task shares with other tasks sharing them.
+/// - `.claim pwm 2` declares the most permits of a semaphore held at once, and
+///   may be given once for each semaphore.
+/// - `.config 4 pull up open_drain drive 10mA initial high` sets up a declared
+///   pin, and may be given once for each pin. Any of `pull` (`floating`, `up`,
+///   `down` or `up_down`), `open_drain`, `drive` (`5mA`, `10mA`, `20mA` or
+///   `40mA`) and `initial` (`high` or `low`) may be left out.
+///
+/// Mistakes are reported as a [`ParseError`] with the line and column they are at.
+pub fn assemble(source: &str) -> anyhow::Result<Task> {
+    Ok(Assembler::default().assemble(source)?)
+}
+
+/// A single word of the source, along with the column it starts at.
+#[derive(Debug, Clone, Copy)]
+struct Token<'s> {
+    text: &'s str,
+    column: usize,
+}
+
+/// A line of the source that holds an instruction.
+#[derive(Debug)]
+struct Line<'s> {
+    number: usize,
+    tokens: Vec<Token<'s>>,
+}
+
+/// Holds everything read from the source while assembling a task.
+///
+/// Labels can be used before they are defined, so instructions are only parsed
+/// once every line has been read.
+#[derive(Debug, Default)]
+struct Assembler<'s> {
+    /// The name of the task, along with the line it was given on.
+    name: Option<(usize, String)>,
+    priority: Option<TaskPriority>,
+    shots: Option<Shot>,
+    pins: Option<Vec<i32>>,
+    inputs: Option<Vec<i32>>,
+    shared: Option<Vec<i32>>,
+    claims: BTreeMap<String, usize>,
+    /// The configuration of each pin, along with the line and column it was given at.
+    configs: BTreeMap<i32, (usize, usize, PinConfig)>,
+    labels: HashMap<&'s str, usize>,
+    instructions: Vec<Line<'s>>,
+}
+
+impl<'s> Assembler<'s> {
+    fn assemble(mut self, source: &'s str) -> Result<Task> {
+        for (index, line) in source.lines().enumerate() {
+            let number = index + 1;
+            let tokens = tokenize(line, number)?;
+            let mut tokens = tokens.as_slice();
+
+            while let Some((token, rest)) = tokens.split_first() {
+                let Some(label) = token.text.strip_suffix(':') else {
+                    break;
+                };
+                self.label(number, *token, label)?;
+                tokens = rest;
+            }
+
+            match tokens.first() {
+                None => (),
+                Some(token) if token.text.starts_with('.') => self.directive(number, tokens)?,
+                Some(_) => self.instructions.push(Line {
+                    number,
+                    tokens: tokens.to_vec(),
+                }),
+            }
+        }
+
+        let steps = self
+            .instructions
+            .iter()
+            .map(|line| self.instruction(line))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (name_line, name) = self.name.ok_or_else(|| {
+            ParseError::new(1, 1, "The task has no name, add a '.name' directive.")
+        })?;
+
+        // Every jump refers to a label, so the jumps are always in bounds, but they
+        // are checked here anyway so that a mistake is reported at its own step.
+        for (line, step) in self.instructions.iter().zip(&steps) {
+            if let Some(target) = step.jump_target().filter(|target| *target > steps.len()) {
+                return Err(ParseError::new(
+                    line.number,
+                    line.tokens[0].column,
+                    format!(
+                        "Jumps to step {target}, but there are only {} steps.",
+                        steps.len()
+                    ),
+                ));
+            }
+        }
+
+        let mut pins = self.pins.unwrap_or_default();
+        let mut modes = BTreeMap::new();
+        for (declared, mode) in [
+            (self.inputs, AccessMode::Input),
+            (self.shared, AccessMode::Shared),
+        ] {
+            for pin in declared.unwrap_or_default() {
+                pins.push(pin);
+                modes.insert(pin, mode);
+            }
+        }
+
+        let mut configs = BTreeMap::new();
+        for (pin, (number, column, config)) in self.configs {
+            if !pins.contains(&pin) {
+                return Err(ParseError::new(
+                    number,
+                    column,
+                    format!("Pin {pin} is configured, but has not been declared."),
+                ));
+            }
+            if !config.is_default() {
+                configs.insert(pin, config);
+            }
+        }
+
+        let context = TaskContext::builder()
+            .name(name)
+            .priority(self.priority.unwrap_or(TaskPriority::Low))
+            .pins_used(pins)
+            .pin_modes(modes)
+            .pin_configs(configs)
+            .max_claims(self.claims)
+            .build();
+
+        // Anything else wrong with the task as a whole is reported at its name.
+        Task::builder()
+            .context(context)
+            .steps(steps)
+            .shots(self.shots.unwrap_or(Shot::Infinity))
+            .build()
+            .map_err(|error| ParseError::new(name_line, 1, error.to_string()))
+    }
+
+    /// Define a label pointing at the next instruction.
+    fn label(&mut self, number: usize, token: Token<'s>, label: &'s str) -> Result<()> {
+        if !is_identifier(label) {
+            return Err(ParseError::new(
+                number,
+                token.column,
+                format!("'{label}' is not a valid label name."),
+            ));
+        }
+
+        if self.labels.insert(label, self.instructions.len()).is_some() {
+            return Err(ParseError::new(
+                number,
+                token.column,
+                format!("Label '{label}' has already been defined."),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Read a directive describing the task itself.
+    fn directive(&mut self, number: usize, tokens: &[Token<'s>]) -> Result<()> {
+        let mut operands = Operands::new(number, tokens);
+        let directive = operands.next("a directive")?;
+
+        match directive.text {
+            ".name" => {
+                let name = operands.string("the name of the task")?;
+                Self::once(&mut self.name, (number, name), number, directive)?;
+            }
+            ".priority" => {
+                let priority = match operands.peek().map(|token| token.text) {
+                    Some("low") => operands.skip(TaskPriority::Low),
+                    Some("normal") => operands.skip(TaskPriority::Normal),
+                    Some("high") => operands.skip(TaskPriority::High),
+                    _ => TaskPriority::from(operands.number::<u8>("a priority")?),
+                };
+                Self::once(&mut self.priority, priority, number, directive)?;
+            }
+            ".shots" => {
+                let shots = if operands.optional("infinity") {
+                    Shot::Infinity
+                } else {
+                    Shot::Custom(operands.number("a number of shots")?)
+                };
+                Self::once(&mut self.shots, shots, number, directive)?;
+            }
+            ".pins" | ".inputs" | ".shared" => {
+                let mut pins = vec![];
+                while pins.is_empty() || operands.peek().is_some() {
+                    let token = operands.next("a pin number")?;
+                    let pin = operands.parse_number(token, "a pin number")?;
+                    let declared = [&self.pins, &self.inputs, &self.shared]
+                        .into_iter()
+                        .flatten()
+                        .chain([&pins])
+                        .any(|declared| declared.contains(&pin));
+                    if declared {
+                        return Err(
+                            operands.error(token, format!("Pin {pin} is declared more than once."))
+                        );
+                    }
+                    pins.push(pin);
+                }
+                let slot = match directive.text {
+                    ".pins" => &mut self.pins,
+                    ".inputs" => &mut self.inputs,
+                    _ => &mut self.shared,
+                };
+                Self::once(slot, pins, number, directive)?;
+            }
+            ".claim" => {
+                let semaphore = operands.name("a semaphore name")?;
+                let permits = operands.number("a number of permits")?;
+                if self.claims.contains_key(&semaphore) {
+                    return Err(operands.error(
+                        directive,
+                        format!("Semaphore '{semaphore}' has already been claimed."),
+                    ));
+                }
+                self.claims.insert(semaphore, permits);
+            }
+            ".config" => {
+                let pin = operands.number("a pin number")?;
+                let config = operands.config()?;
+                if self.configs.contains_key(&pin) {
+                    return Err(operands
+                        .error(directive, format!("Pin {pin} has already been configured.")));
+                }
+                self.configs.insert(pin, (number, directive.column, config));
+            }
+            other => {
+                return Err(operands.error(directive, format!("Unknown directive '{other}'.")));
+            }
+        }
+
+        operands.finish()
+    }
+
+    /// Save the value of a directive, which may only be given once.
+    fn once<T>(slot: &mut Option<T>, value: T, number: usize, directive: Token) -> Result<()> {
+        if slot.replace(value).is_some() {
+            return Err(ParseError::new(
+                number,
+                directive.column,
+                format!("The '{}' directive has already been given.", directive.text),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Parse a single instruction into the step that it stands for.
+    fn instruction(&self, line: &Line<'s>) -> Result<TaskStep> {
+        let mut operands = Operands::new(line.number, &line.tokens);
+        let mnemonic = operands.next("an instruction")?;
+
+        let step = match mnemonic.text {
+            "high" | "low" => {
+                let level = if mnemonic.text == "high" {
+                    Level::High
+                } else {
+                    Level::Low
+                };
+                match operands.optional_register()? {
+                    Some(register) => TaskStep::WriteGPIOIndirect(register, level),
+                    None => TaskStep::WriteGPIO(operands.number("a pin number")?, level),
+                }
+            }
+            "read" => match operands.optional_register()? {
+                Some(pin) => {
+                    operands.keyword("->")?;
+                    TaskStep::ReadGPIOIndirect(pin, operands.register()?)
+                }
+                None => {
+                    let pin = operands.number("a pin number")?;
+                    operands.keyword("->")?;
+                    TaskStep::ReadGPIO(pin, operands.register()?)
+                }
+            },
+            "wait" => match operands.optional_register()? {
+                Some(register) => TaskStep::YieldIndirect(register),
+                None => TaskStep::Yield(operands.duration()?),
+            },
+            "yield" => TaskStep::YieldNow,
+            "sleep" => {
+                let duration = operands.duration()?;
+                operands.keyword("after")?;
+                let anchor = match operands.next("'start' or 'mark'")? {
+                    Token { text: "start", .. } => TimeAnchor::Start,
+                    Token { text: "mark", .. } => TimeAnchor::Mark,
+                    token => return Err(operands.expected(token, "'start' or 'mark'")),
+                };
+                TaskStep::SleepUntil(duration, anchor)
+            }
+            "mark" => TaskStep::MarkTime,
+            "wait_level" => {
+                let pin = operands.number("a pin number")?;
+                let level = operands.level()?;
+                let timeout = operands.timeout()?;
+                TaskStep::WaitLevel(pin, level, timeout, operands.destination()?)
+            }
+            "wait_edge" => {
+                let pin = operands.number("a pin number")?;
+                let edge = match operands.next("an edge")? {
+                    Token { text: "rising", .. } => Edge::Rising,
+                    Token {
+                        text: "falling", ..
+                    } => Edge::Falling,
+                    Token { text: "any", .. } => Edge::Any,
+                    token => return Err(operands.expected(token, "'rising', 'falling' or 'any'")),
+                };
+                let timeout = operands.timeout()?;
+                TaskStep::WaitEdge(pin, edge, timeout, operands.destination()?)
+            }
+            "send" => {
+                let channel = operands.name("a channel name")?;
+                TaskStep::Send(channel, operands.number("a value")?)
+            }
+            "recv" => {
+                let channel = operands.name("a channel name")?;
+                operands.keyword("->")?;
+                TaskStep::Recv(channel, operands.register()?)
+            }
+            "store" => {
+                let register = operands.register()?;
+                operands.keyword("->")?;
+                TaskStep::Store(operands.name("a variable name")?, register)
+            }
+            "load" => {
+                let variable = operands.name("a variable name")?;
+                operands.keyword("->")?;
+                TaskStep::Load(operands.register()?, variable)
+            }
+            "set" | "add" | "sub" => {
+                let register = operands.register()?;
+                let operand = operands.operand()?;
+                match mnemonic.text {
+                    "set" => TaskStep::Set(register, operand),
+                    "add" => TaskStep::Add(register, operand),
+                    _ => TaskStep::Sub(register, operand),
+                }
+            }
+            "cmp" => {
+                let register = operands.register()?;
+                let operand = operands.operand()?;
+                operands.keyword("->")?;
+                TaskStep::Compare(operands.register()?, register, operand)
+            }
+            "jump" => TaskStep::Jump(operands.label(&self.labels)?),
+            "jump_if" => {
+                let register = operands.register()?;
+                let condition = match operands.next("a condition")? {
+                    Token { text: "eq", .. } => Condition::Equal,
+                    Token { text: "ne", .. } => Condition::NotEqual,
+                    Token { text: "lt", .. } => Condition::Less,
+                    Token { text: "le", .. } => Condition::LessOrEqual,
+                    Token { text: "gt", .. } => Condition::Greater,
+                    Token { text: "ge", .. } => Condition::GreaterOrEqual,
+                    token => {
+                        return Err(operands.expected(token, "'eq', 'ne', 'lt', 'le', 'gt' or 'ge'"))
+                    }
+                };
+                TaskStep::JumpIf(register, condition, operands.label(&self.labels)?)
+            }
+            "loop" => {
+                let register = operands.register()?;
+                TaskStep::Loop(register, operands.label(&self.labels)?)
+            }
+            "call" => TaskStep::Call(operands.name("a routine name")?),
+            "ret" => TaskStep::Return,
+            "acquire" => TaskStep::Acquire(operands.name("a semaphore name")?),
+            "release" => TaskStep::Release(operands.name("a semaphore name")?),
+            "set_bits" | "clear_bits" => {
+                let group = operands.name("an event group name")?;
+                let mask = operands.number("a mask")?;
+                if mnemonic.text == "set_bits" {
+                    TaskStep::SetBits(group, mask)
+                } else {
+                    TaskStep::ClearBits(group, mask)
+                }
+            }
+            "wait_bits" => {
+                let group = operands.name("an event group name")?;
+                let mask = operands.number("a mask")?;
+                let mode = match operands.next("'all' or 'any'")? {
+                    Token { text: "all", .. } => WaitMode::All,
+                    Token { text: "any", .. } => WaitMode::Any,
+                    token => return Err(operands.expected(token, "'all' or 'any'")),
+                };
+                TaskStep::WaitBits(group, mask, mode, operands.optional("clear"))
+            }
+            other => {
+                return Err(operands.error(mnemonic, format!("Unknown instruction '{other}'.")));
+            }
+        };
+
+        operands.finish()?;
+        Ok(step)
+    }
+}
+
+/// Walks through the operands of a single directive or instruction.
+struct Operands<'t, 's> {
+    line: usize,
+    tokens: std::slice::Iter<'t, Token<'s>>,
+    /// The column just past the last token, where a missing operand is reported.
+    end: usize,
+}
+
+impl<'t, 's> Operands<'t, 's> {
+    fn new(line: usize, tokens: &'t [Token<'s>]) -> Self {
+        let end = tokens
+            .last()
+            .map_or(1, |token| token.column + token.text.chars().count());
+
+        Self {
+            line,
+            tokens: tokens.iter(),
+            end,
+        }
+    }
+
+    fn error(&self, token: Token, message: impl Into<String>) -> ParseError {
+        ParseError::new(self.line, token.column, message)
+    }
+
+    fn expected(&self, token: Token, expected: &str) -> ParseError {
+        self.error(
+            token,
+            format!("Expected {expected}, but found '{}'.", token.text),
+        )
+    }
+
+    fn peek(&self) -> Option<Token<'s>> {
+        self.tokens.clone().next().copied()
+    }
+
+    /// Move past the next token, returning the value it was read as.
+    fn skip<T>(&mut self, value: T) -> T {
+        self.tokens.next();
+        value
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token<'s>> {
+        self.tokens.next().copied().ok_or_else(|| {
+            ParseError::new(
+                self.line,
+                self.end,
+                format!("Expected {expected}, but the line ended."),
+            )
+        })
+    }
+
+    /// Check that every operand has been read.
+    fn finish(&mut self) -> Result<()> {
+        match self.tokens.next() {
+            Some(token) => Err(self.error(*token, format!("Unexpected '{}'.", token.text))),
+            None => Ok(()),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        let token = self.next(&format!("'{keyword}'"))?;
+        (token.text == keyword)
+            .then_some(())
+            .ok_or_else(|| self.expected(token, &format!("'{keyword}'")))
+    }
+
+    /// Move past the next token if it is the given keyword.
+    fn optional(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token.text == keyword);
+        if found {
+            self.tokens.next();
+        }
+        found
+    }
+
+    fn number<T: TryFrom<i64>>(&mut self, expected: &str) -> Result<T> {
+        let token = self.next(expected)?;
+        self.parse_number(token, expected)
+    }
+
+    /// Read a token that has already been taken as a number.
+    fn parse_number<T: TryFrom<i64>>(&self, token: Token, expected: &str) -> Result<T> {
+        let value = parse_integer(token.text).ok_or_else(|| self.expected(token, expected))?;
+        T::try_from(value).map_err(|_| {
+            self.error(
+                token,
+                format!("'{}' is out of range for {expected}.", token.text),
+            )
+        })
+    }
+
+    fn string(&mut self, expected: &str) -> Result<String> {
+        let token = self.next(expected)?;
+        unquote(token.text).ok_or_else(|| self.expected(token, &format!("{expected} in quotes")))
+    }
+
+    /// Read a name, which is either written as is if it is an identifier, or in
+    /// quotes if it is not.
+    fn name(&mut self, expected: &str) -> Result<String> {
+        let token = self.next(expected)?;
+        if is_identifier(token.text) {
+            return Ok(token.text.to_string());
+        }
+
+        unquote(token.text).ok_or_else(|| self.expected(token, expected))
+    }
+
+    fn label(&mut self, labels: &HashMap<&str, usize>) -> Result<usize> {
+        let token = self.next("a label")?;
+        labels.get(token.text).copied().ok_or_else(|| {
+            self.error(
+                token,
+                format!("Label '{}' has not been defined.", token.text),
+            )
+        })
+    }
+
+    fn register(&mut self) -> Result<Register> {
+        let token = self.next("a register")?;
+        parse_register(token.text)
+            .ok_or_else(|| self.expected(token, "a register from r0 to r7 or b0 to b7"))
+    }
+
+    /// Read the next token as a register, if it is written like one.
+    fn optional_register(&mut self) -> Result<Option<Register>> {
+        match self.peek() {
+            Some(token) if looks_like_register(token.text) => self.register().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        if let Some(register) = self.optional_register()? {
+            return Ok(Operand::Register(register));
+        }
+
+        match self.peek().map(|token| token.text) {
+            Some("true") => Ok(self.skip(Operand::Immediate(Value::Bool(true)))),
+            Some("false") => Ok(self.skip(Operand::Immediate(Value::Bool(false)))),
+            _ => Ok(Operand::Immediate(Value::Int(
+                self.number("a register or a value")?,
+            ))),
+        }
+    }
+
+    fn level(&mut self) -> Result<Level> {
+        match self.next("'high' or 'low'")? {
+            Token { text: "high", .. } => Ok(Level::High),
+            Token { text: "low", .. } => Ok(Level::Low),
+            token => Err(self.expected(token, "'high' or 'low'")),
+        }
+    }
+
+    /// Read the options of a pin configuration up to the end of the line, each of
+    /// which may be given at most once.
+    fn config(&mut self) -> Result<PinConfig> {
+        let mut config = PinConfig::default();
+        let mut given = vec![];
+
+        while let Some(option) = self.peek() {
+            self.tokens.next();
+            if given.contains(&option.text) {
+                return Err(self.error(
+                    option,
+                    format!("The '{}' option has already been given.", option.text),
+                ));
+            }
+            given.push(option.text);
+
+            match option.text {
+                "pull" => {
+                    let pull = match self.next("a pull mode")? {
+                        Token {
+                            text: "floating", ..
+                        } => PullMode::Floating,
+                        Token { text: "up", .. } => PullMode::Up,
+                        Token { text: "down", .. } => PullMode::Down,
+                        Token {
+                            text: "up_down", ..
+                        } => PullMode::UpDown,
+                        token => {
+                            return Err(
+                                self.expected(token, "'floating', 'up', 'down' or 'up_down'")
+                            )
+                        }
+                    };
+                    config.set_pull(pull);
+                }
+                "open_drain" => {
+                    config.set_open_drain(true);
+                }
+                "drive" => {
+                    let drive = match self.next("a drive capability")? {
+                        Token { text: "5mA", .. } => DriveCapability::I5mA,
+                        Token { text: "10mA", .. } => DriveCapability::I10mA,
+                        Token { text: "20mA", .. } => DriveCapability::I20mA,
+                        Token { text: "40mA", .. } => DriveCapability::I40mA,
+                        token => {
+                            return Err(self.expected(token, "'5mA', '10mA', '20mA' or '40mA'"))
+                        }
+                    };
+                    config.set_drive(Some(drive));
+                }
+                "initial" => {
+                    let level = self.level()?;
+                    config.set_initial_level(Some(level));
+                }
+                _ => {
+                    return Err(self.expected(option, "'pull', 'open_drain', 'drive' or 'initial'"))
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Read a duration written in milliseconds or seconds, such as `250ms` or `2s`.
+    fn duration(&mut self) -> Result<u32> {
+        let expected = "a duration such as 1000ms or 1s";
+        let token = self.next(expected)?;
+
+        let (value, scale) = match token.text.strip_suffix("ms") {
+            Some(value) => (value, 1),
+            None => match token.text.strip_suffix('s') {
+                Some(value) => (value, 1000),
+                None => return Err(self.expected(token, expected)),
+            },
+        };
+
+        let value = parse_integer(value).ok_or_else(|| self.expected(token, expected))?;
+        value
+            .checked_mul(scale)
+            .and_then(|ms| u32::try_from(ms).ok())
+            .ok_or_else(|| {
+                self.error(
+                    token,
+                    format!("'{}' is out of range for a duration.", token.text),
+                )
+            })
+    }
+
+    /// Read an optional `timeout <duration>` at the end of a wait.
+    fn timeout(&mut self) -> Result<Option<u32>> {
+        match self.optional("timeout") {
+            true => self.duration().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Read an optional register to store a result in, such as `-> b0`.
+    fn destination(&mut self) -> Result<Option<Register>> {
+        match self.optional("->") {
+            true => self.register().map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Split a line into tokens, dropping any comment at the end of it.
+///
+/// Tokens are separated by whitespace or commas, and text in double quotes is
+/// kept together as a single token, where `\"` does not close the quotes.
+fn tokenize(line: &str, number: usize) -> Result<Vec<Token<'_>>> {
+    let mut tokens = vec![];
+    let mut characters = line.char_indices().enumerate().peekable();
+
+    while let Some((column, (start, character))) = characters.next() {
+        let column = column + 1;
+        match character {
+            ';' => break,
+            character if character.is_whitespace() || character == ',' => (),
+            '"' => {
+                let mut escaped = false;
+                let end = characters
+                    .find(|(_, (_, character))| {
+                        let closes = *character == '"' && !escaped;
+                        escaped = *character == '\\' && !escaped;
+                        closes
+                    })
+                    .map(|(_, (end, _))| end)
+                    .ok_or_else(|| ParseError::new(number, column, "Missing the closing quote."))?;
+                tokens.push(Token {
+                    text: &line[start..=end],
+                    column,
+                });
+            }
+            _ => {
+                let mut end = start + character.len_utf8();
+                while let Some((_, (index, character))) =
+                    characters.next_if(|(_, (_, character))| {
+                        !character.is_whitespace() && !matches!(character, ',' | ';' | '"')
+                    })
+                {
+                    end = index + character.len_utf8();
+                }
+                tokens.push(Token {
+                    text: &line[start..end],
+                    column,
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Take the text out of a token in double quotes, replacing the escapes `\\`,
+/// `\"`, `\n`, `\r` and `\t` with what they stand for.
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut characters = inner.chars();
+    while let Some(character) = characters.next() {
+        unquoted.push(match character {
+            '\\' => match characters.next()? {
+                '\\' => '\\',
+                '"' => '"',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                _ => return None,
+            },
+            character => character,
+        });
+    }
+    Some(unquoted)
+}
+
+/// Parse a decimal, hexadecimal (`0x`) or binary (`0b`) integer, which may be negative.
+fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits
+            .chars()
+            .all(|character| character.is_ascii_digit())
+            .then(|| digits.parse().ok())??
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn looks_like_register(text: &str) -> bool {
+    let mut characters = text.chars();
+    matches!(characters.next(), Some('r' | 'b'))
+        && !characters.as_str().is_empty()
+        && characters.all(|character| character.is_ascii_digit())
+}
+
+fn parse_register(text: &str) -> Option<Register> {
+    if !looks_like_register(text) {
+        return None;
+    }
+
+    let index: u8 = text[1..].parse().ok()?;
+    match &text[..1] {
+        "r" => ((index as usize) < RegisterFile::INTS).then_some(Register::Int(index)),
+        _ => ((index as usize) < RegisterFile::BOOLS).then_some(Register::Bool(index)),
+    }
+}
+
+pub(super) fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    characters
+        .next()
+        .is_some_and(|character| character.is_ascii_alphabetic() || character == '_')
+        && characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
+}
+
+#[cfg(test)]
+pub mod tests {
+    use anyhow::Context;
+
+    use super::assemble;
+    use crate::{
+        asm::ParseError,
+        task::{Shot, TaskPriority},
+        testing,
+    };
+
+    testing::tests!(
+        defaults_to_low_priority_and_infinite_shots,
+        reports_unknown_instructions,
+        reports_bad_durations,
+        reports_undefined_labels,
+        reports_duplicate_labels,
+        reports_unknown_registers,
+        rejects_pins_declared_twice,
+    );
+
+    /// Assemble a source that should fail, returning the line, column and message
+    /// of the mistake.
+    fn error(source: &str) -> anyhow::Result<(usize, usize, String)> {
+        let error = assemble(source)
+            .err()
+            .context("The source should not assemble!")?;
+        let error = error
+            .downcast_ref::<ParseError>()
+            .context("The mistake should be a parse error!")?;
+        Ok((*error.line(), *error.column(), error.message().clone()))
+    }
+
+    fn defaults_to_low_priority_and_infinite_shots() -> anyhow::Result<()> {
+        let task = assemble(".name \"Test\"\nyield\n")?;
+        assert_eq!(*task.context().priority(), TaskPriority::Low);
+        assert_eq!(*task.shots(), Shot::Infinity);
+
+        // The same defaults as a task built with `task!`.
+        let built = util::task! { name: "Test", steps: { yield; } }?;
+        assert_eq!(built.context().priority(), task.context().priority());
+        assert_eq!(built.shots(), task.shots());
+        Ok(())
+    }
+
+    fn reports_unknown_instructions() -> anyhow::Result<()> {
+        let (line, column, message) = error(".name \"Test\"\nyield\n  blink 4\n")?;
+        assert_eq!((line, column), (3, 3));
+        assert_eq!(message, "Unknown instruction 'blink'.");
+        Ok(())
+    }
+
+    fn reports_bad_durations() -> anyhow::Result<()> {
+        let (line, column, message) = error(".name \"Test\"\nwait 100\n")?;
+        assert_eq!((line, column), (2, 6));
+        assert!(message.contains("'100'"));
+
+        let (line, column, _) = error(".name \"Test\"\n\nsleep 5000000s after start\n")?;
+        assert_eq!((line, column), (3, 7));
+        Ok(())
+    }
+
+    fn reports_undefined_labels() -> anyhow::Result<()> {
+        let source = ".name \"Test\"\nstart: yield\nloop r0 stat ; Typo\n";
+        let (line, column, message) = error(source)?;
+        assert_eq!((line, column), (3, 9));
+        assert_eq!(message, "Label 'stat' has not been defined.");
+        Ok(())
+    }
+
+    fn reports_duplicate_labels() -> anyhow::Result<()> {
+        let source = ".name \"Test\"\nstart: yield\nhigh 2\n    start: low 2\n";
+        let (line, column, message) = error(source)?;
+        assert_eq!((line, column), (4, 5));
+        assert_eq!(message, "Label 'start' has already been defined.");
+        Ok(())
+    }
+
+    fn reports_unknown_registers() -> anyhow::Result<()> {
+        let (line, column, message) = error(".name \"Test\"\nread 4 -> r8\n")?;
+        assert_eq!((line, column), (2, 11));
+        assert!(message.contains("r0 to r7"));
+
+        let (line, column, _) = error(".name \"Test\"\nset x0 1\n")?;
+        assert_eq!((line, column), (2, 5));
+        Ok(())
+    }
+
+    fn rejects_pins_declared_twice() -> anyhow::Result<()> {
+        let source = ".name \"Test\"\n.pins 2, 4\n.inputs 3, 4\nyield\n";
+        let (line, column, message) = error(source)?;
+        assert_eq!((line, column), (3, 12));
+        assert_eq!(message, "Pin 4 is declared more than once.");
+
+        let (line, column, _) = error(".name \"Test\"\n.shared 5 5\nyield\n")?;
+        assert_eq!((line, column), (2, 11));
+        Ok(())
+    }
+}
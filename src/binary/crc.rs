@@ -0,0 +1,15 @@
+/// Compute the CRC-32 of the given bytes, as used by zlib and Ethernet.
+///
+/// This is done bit by bit rather than with a lookup table, as images are small
+/// and the table would cost a kilobyte of flash.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,458 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+use esp_idf_hal::gpio::Level;
+
+use super::{crc::crc32, Opcode, MAGIC, VERSION};
+use crate::{
+    resource::{AccessMode, DriveCapability, Edge, PinConfig, PullMode, WaitMode},
+    task::{
+        Condition, Operand, Register, RegisterFile, Shot, Task, TaskContext, TaskPriority,
+        TaskStep, TimeAnchor, Value,
+    },
+};
+
+/// Decode a binary image made by [`encode`](super::encode) back into a task.
+///
+/// The image is checked from the outside in: the magic header, then the format
+/// version, then the CRC, and only then the task itself. Errors name the byte
+/// offset that they were found at, counting from the start of the image.
+pub fn decode(image: &[u8]) -> anyhow::Result<Task> {
+    let header = MAGIC.len() + 1;
+    (image.len() >= header + 4).then_some(()).context(format!(
+        "Image is only {} bytes long, which is too short to hold a task!",
+        image.len()
+    ))?;
+
+    (image[..MAGIC.len()] == MAGIC)
+        .then_some(())
+        .context("Image does not start with the magic header, so it is not a task!")?;
+
+    let version = image[MAGIC.len()];
+    (version == VERSION).then_some(()).context(format!(
+        "Image is in format version {version}, but only version {VERSION} is supported!"
+    ))?;
+
+    let (body, crc) = image.split_at(image.len() - 4);
+    let expected = u32::from_le_bytes(crc.try_into()?);
+    let actual = crc32(body);
+    (actual == expected).then_some(()).context(format!(
+        "Image is corrupt, its CRC is {expected:#010x} but its contents have a CRC of {actual:#010x}!"
+    ))?;
+
+    let mut reader = Reader {
+        bytes: body,
+        position: header,
+    };
+
+    let name = reader.string()?;
+    let priority = TaskPriority::from(reader.byte()?);
+    let shots = match reader.varint()? {
+        0 => Shot::Infinity,
+        count => Shot::Custom((count - 1).try_into()?),
+    };
+
+    let mut pins = vec![];
+    let mut modes = BTreeMap::new();
+    let mut configs = BTreeMap::new();
+    for _ in 0..reader.varint()? {
+        let pin = reader.signed()?;
+        let mode = reader.choice(
+            "access mode",
+            &[AccessMode::Output, AccessMode::Shared, AccessMode::Input],
+        )?;
+        if mode != AccessMode::Output {
+            modes.insert(pin, mode);
+        }
+        let config = reader.pin_config()?;
+        if !config.is_default() {
+            configs.insert(pin, config);
+        }
+        pins.push(pin);
+    }
+
+    let mut claims = BTreeMap::new();
+    for _ in 0..reader.varint()? {
+        let semaphore = reader.string()?;
+        claims.insert(semaphore, reader.index()?);
+    }
+
+    let count = reader.varint()?;
+    let mut steps = vec![];
+    for index in 0..count {
+        let step = reader
+            .step()
+            .context(format!("Could not decode step {index}!"))?;
+        steps.push(step);
+    }
+
+    (reader.position == body.len())
+        .then_some(())
+        .context(format!(
+            "Image has {} unexpected bytes after its last step!",
+            body.len() - reader.position
+        ))?;
+
+    let context = TaskContext::builder()
+        .name(name)
+        .priority(priority)
+        .pins_used(pins)
+        .pin_modes(modes)
+        .pin_configs(configs)
+        .max_claims(claims)
+        .build();
+
+    Task::builder()
+        .context(context)
+        .steps(steps)
+        .shots(shots)
+        .build()
+}
+
+/// Reads the parts of a task from an image, keeping track of where it is.
+#[derive(Debug)]
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        let byte = *self.bytes.get(self.position).context(format!(
+            "Image ended unexpectedly at byte {}!",
+            self.position
+        ))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    /// Read an unsigned number in LEB128, seven bits to a byte.
+    fn varint(&mut self) -> anyhow::Result<u64> {
+        let start = self.position;
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        bail!("Number at byte {start} is too long!")
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let start = self.position;
+        u32::try_from(self.varint()?).context(format!("Number at byte {start} is too large!"))
+    }
+
+    /// Read a signed number that was zigzagged so that small negative numbers stay small.
+    fn signed(&mut self) -> anyhow::Result<i32> {
+        let value = self.u32()?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+
+    fn index(&mut self) -> anyhow::Result<usize> {
+        let start = self.position;
+        usize::try_from(self.varint()?).context(format!("Index at byte {start} is too large!"))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let start = self.position;
+        let length = self.index()?;
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .context(format!(
+                "Text at byte {start} runs past the end of the image!"
+            ))?;
+
+        let text = std::str::from_utf8(&self.bytes[self.position..end])
+            .context(format!("Text at byte {start} is not valid UTF-8!"))?;
+        self.position = end;
+        Ok(text.into())
+    }
+
+    fn bool(&mut self) -> anyhow::Result<bool> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => bail!(
+                "Expected a boolean at byte {}, found {other}!",
+                self.position - 1
+            ),
+        }
+    }
+
+    /// Read a single byte that picks one of the given values.
+    fn choice<T: Copy>(&mut self, what: &str, values: &[T]) -> anyhow::Result<T> {
+        let byte = self.byte()?;
+        values.get(byte as usize).copied().context(format!(
+            "Unknown {what} {byte} at byte {}!",
+            self.position - 1
+        ))
+    }
+
+    fn register(&mut self) -> anyhow::Result<Register> {
+        let byte = self.byte()?;
+        let index = byte & 0x7F;
+        let (register, count) = match byte & 0x80 {
+            0 => (Register::Int(index), RegisterFile::INTS),
+            _ => (Register::Bool(index), RegisterFile::BOOLS),
+        };
+
+        ((index as usize) < count)
+            .then_some(register)
+            .context(format!(
+                "Register {register:?} at byte {} does not exist!",
+                self.position - 1
+            ))
+    }
+
+    /// Read an optional register, where a byte of 0xFF means that there is none.
+    fn optional_register(&mut self) -> anyhow::Result<Option<Register>> {
+        match self.bytes.get(self.position) {
+            Some(0xFF) => {
+                self.position += 1;
+                Ok(None)
+            }
+            _ => self.register().map(Some),
+        }
+    }
+
+    fn operand(&mut self) -> anyhow::Result<Operand> {
+        match self.byte()? {
+            0 => Ok(Operand::Register(self.register()?)),
+            1 => Ok(Operand::Immediate(Value::Int(self.signed()?))),
+            2 => Ok(Operand::Immediate(Value::Bool(self.bool()?))),
+            other => bail!(
+                "Unknown kind of operand {other} at byte {}!",
+                self.position - 1
+            ),
+        }
+    }
+
+    /// Read the configuration of a pin packed into a single byte.
+    fn pin_config(&mut self) -> anyhow::Result<PinConfig> {
+        let byte = self.byte()?;
+        let mut config = PinConfig::default();
+        config.set_pull(
+            [
+                PullMode::Floating,
+                PullMode::Up,
+                PullMode::Down,
+                PullMode::UpDown,
+            ][(byte & 0b11) as usize],
+        );
+        config.set_open_drain(byte & 0b100 != 0);
+        config.set_drive(match (byte >> 3) & 0b111 {
+            0 => None,
+            1 => Some(DriveCapability::I5mA),
+            2 => Some(DriveCapability::I10mA),
+            3 => Some(DriveCapability::I20mA),
+            4 => Some(DriveCapability::I40mA),
+            other => bail!(
+                "Unknown drive capability {other} at byte {}!",
+                self.position - 1
+            ),
+        });
+        config.set_initial_level(match byte >> 6 {
+            0 => None,
+            1 => Some(Level::Low),
+            2 => Some(Level::High),
+            other => bail!(
+                "Unknown initial level {other} at byte {}!",
+                self.position - 1
+            ),
+        });
+        Ok(config)
+    }
+
+    fn level(&mut self) -> anyhow::Result<Level> {
+        Ok(Level::from(self.bool()?))
+    }
+
+    /// Read an optional timeout, where zero means that there is none.
+    ///
+    /// The timeout is written one higher, so a timeout of `u32::MAX` needs more
+    /// than 32 bits and is read as a wider number first.
+    fn timeout(&mut self) -> anyhow::Result<Option<u32>> {
+        let start = self.position;
+        self.varint()?
+            .checked_sub(1)
+            .map(u32::try_from)
+            .transpose()
+            .context(format!("Timeout at byte {start} is too large!"))
+    }
+
+    fn step(&mut self) -> anyhow::Result<TaskStep> {
+        let start = self.position;
+        let opcode = Opcode::try_from(self.byte()?)
+            .context(format!("Step at byte {start} cannot be decoded!"))?;
+
+        Ok(match opcode {
+            Opcode::ReadGPIO => TaskStep::ReadGPIO(self.signed()?, self.register()?),
+            Opcode::WriteGPIO => TaskStep::WriteGPIO(self.signed()?, self.level()?),
+            Opcode::Yield => TaskStep::Yield(self.u32()?),
+            Opcode::YieldNow => TaskStep::YieldNow,
+            Opcode::SleepUntil => TaskStep::SleepUntil(
+                self.u32()?,
+                self.choice("time anchor", &[TimeAnchor::Start, TimeAnchor::Mark])?,
+            ),
+            Opcode::MarkTime => TaskStep::MarkTime,
+            Opcode::WaitLevel => TaskStep::WaitLevel(
+                self.signed()?,
+                self.level()?,
+                self.timeout()?,
+                self.optional_register()?,
+            ),
+            Opcode::WaitEdge => TaskStep::WaitEdge(
+                self.signed()?,
+                self.choice("edge", &[Edge::Rising, Edge::Falling, Edge::Any])?,
+                self.timeout()?,
+                self.optional_register()?,
+            ),
+            Opcode::Send => TaskStep::Send(self.string()?, self.signed()?),
+            Opcode::Recv => TaskStep::Recv(self.string()?, self.register()?),
+            Opcode::Store => TaskStep::Store(self.string()?, self.register()?),
+            Opcode::Load => TaskStep::Load(self.register()?, self.string()?),
+            Opcode::Set => TaskStep::Set(self.register()?, self.operand()?),
+            Opcode::Add => TaskStep::Add(self.register()?, self.operand()?),
+            Opcode::Sub => TaskStep::Sub(self.register()?, self.operand()?),
+            Opcode::Compare => {
+                TaskStep::Compare(self.register()?, self.register()?, self.operand()?)
+            }
+            Opcode::Jump => TaskStep::Jump(self.index()?),
+            Opcode::JumpIf => TaskStep::JumpIf(
+                self.register()?,
+                self.choice(
+                    "condition",
+                    &[
+                        Condition::Equal,
+                        Condition::NotEqual,
+                        Condition::Less,
+                        Condition::LessOrEqual,
+                        Condition::Greater,
+                        Condition::GreaterOrEqual,
+                    ],
+                )?,
+                self.index()?,
+            ),
+            Opcode::Loop => TaskStep::Loop(self.register()?, self.index()?),
+            Opcode::Call => TaskStep::Call(self.string()?),
+            Opcode::Return => TaskStep::Return,
+            Opcode::ReadGPIOIndirect => {
+                TaskStep::ReadGPIOIndirect(self.register()?, self.register()?)
+            }
+            Opcode::WriteGPIOIndirect => {
+                TaskStep::WriteGPIOIndirect(self.register()?, self.level()?)
+            }
+            Opcode::YieldIndirect => TaskStep::YieldIndirect(self.register()?),
+            Opcode::Acquire => TaskStep::Acquire(self.string()?),
+            Opcode::Release => TaskStep::Release(self.string()?),
+            Opcode::SetBits => TaskStep::SetBits(self.string()?, self.u32()?),
+            Opcode::ClearBits => TaskStep::ClearBits(self.string()?, self.u32()?),
+            Opcode::WaitBits => TaskStep::WaitBits(
+                self.string()?,
+                self.u32()?,
+                self.choice("wait mode", &[WaitMode::All, WaitMode::Any])?,
+                self.bool()?,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::decode;
+    use crate::{
+        binary::{crc::crc32, encoder::tests::GOLDEN},
+        testing,
+    };
+
+    testing::tests!(
+        rejects_wrong_magic,
+        rejects_unknown_version,
+        rejects_unknown_opcode,
+        rejects_bad_crc,
+        rejects_short_images,
+        rejects_timeouts_past_u32
+    );
+
+    /// Check that an image fails to decode, with an error that mentions `reason`.
+    fn rejects(image: &[u8], reason: &str) -> anyhow::Result<()> {
+        match decode(image) {
+            Ok(task) => anyhow::bail!("Decoded {task:?} when it should be rejected."),
+            Err(error) => {
+                let message = format!("{error:?}");
+                assert!(
+                    message.contains(reason),
+                    "{message} should mention '{reason}'."
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Replace the CRC at the end of an image with one that matches its contents.
+    fn reseal(image: &mut [u8]) {
+        let (body, crc) = image.split_at_mut(image.len() - 4);
+        crc.copy_from_slice(&crc32(body).to_le_bytes());
+    }
+
+    fn rejects_wrong_magic() -> anyhow::Result<()> {
+        let mut image = GOLDEN;
+        image[0] = b'S';
+        reseal(&mut image);
+        rejects(&image, "magic header")
+    }
+
+    fn rejects_unknown_version() -> anyhow::Result<()> {
+        let mut image = GOLDEN;
+        image[4] = 0xFE;
+        reseal(&mut image);
+        rejects(&image, "format version 254")
+    }
+
+    fn rejects_unknown_opcode() -> anyhow::Result<()> {
+        // The opcode of the first step, after the header, name, priority, shots,
+        // pin, claims and step count.
+        let mut image = GOLDEN;
+        assert_eq!(image[19], 0x01);
+        image[19] = 0xFE;
+        reseal(&mut image);
+        rejects(&image, "Step at byte 19")
+    }
+
+    fn rejects_bad_crc() -> anyhow::Result<()> {
+        let mut image = GOLDEN;
+        image[7] ^= 0x20;
+        rejects(&image, "corrupt")?;
+
+        let mut image = GOLDEN;
+        image[GOLDEN.len() - 1] ^= 0x01;
+        rejects(&image, "corrupt")
+    }
+
+    fn rejects_short_images() -> anyhow::Result<()> {
+        rejects(&GOLDEN[..8], "too short")?;
+
+        // A whole image cut short, with its CRC fixed up to match.
+        let mut image = GOLDEN[..GOLDEN.len() - 8].to_vec();
+        image.extend_from_slice(&[0; 4]);
+        reseal(&mut image);
+        assert!(decode(&image).is_err());
+        Ok(())
+    }
+
+    fn rejects_timeouts_past_u32() -> anyhow::Result<()> {
+        // wait_level 0 high with a timeout one past u32::MAX, which is written
+        // as u32::MAX + 2.
+        let mut image = GOLDEN[..18].to_vec();
+        image.extend_from_slice(&[0x01, 0x06, 0x00, 0x01, 0x81, 0x80, 0x80, 0x80, 0x10, 0xFF]);
+        image.extend_from_slice(&[0; 4]);
+        reseal(&mut image);
+        rejects(&image, "Timeout at byte 22")
+    }
+}
@@ -0,0 +1,368 @@
+use esp_idf_hal::gpio::Level;
+
+use super::{crc::crc32, Opcode, MAGIC, VERSION};
+use crate::{
+    resource::{AccessMode, DriveCapability, Edge, PinConfig, PullMode, WaitMode},
+    task::{Condition, Operand, Register, Shot, Task, TaskStep, TimeAnchor, Value},
+};
+
+/// Encode a task into a binary image that [`decode`](super::decode) loads back.
+///
+/// Only the parts of the task that describe it are kept, so the image holds no
+/// state from running the task, and a new id is given to the task on loading.
+///
+/// A task named "Blink" that runs forever on pin 2 encodes to these bytes:
+///
+/// ```text
+/// 54 41 53 4b 05                 magic, version
+/// 05 42 6c 69 6e 6b 40 00        name, priority, shots
+/// 01 04 00 00                    pins, with their access modes and configurations
+/// 00                             claims
+/// 04                             step count
+/// 01 04 01                       high 2
+/// 02 e8 07                       wait 1000ms
+/// 01 04 00                       low 2
+/// 02 e8 07                       wait 1000ms
+/// 1e 7c 39 21                    crc
+/// ```
+pub fn encode(task: &Task) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.bytes.extend_from_slice(&MAGIC);
+    writer.byte(VERSION);
+
+    let context = task.context();
+    writer.string(context.name());
+    writer.byte(u8::from(*context.priority()));
+    writer.varint(match task.shots() {
+        Shot::Infinity => 0,
+        Shot::Custom(count) => *count as u64 + 1,
+    });
+
+    writer.varint(context.pins_used().len() as u64);
+    for pin in context.pins_used() {
+        writer.signed(*pin);
+        writer.byte(match context.pin_mode(*pin) {
+            AccessMode::Output => 0,
+            AccessMode::Shared => 1,
+            AccessMode::Input => 2,
+        });
+        writer.pin_config(&context.pin_config(*pin));
+    }
+
+    writer.varint(context.max_claims().len() as u64);
+    for (semaphore, permits) in context.max_claims() {
+        writer.string(semaphore);
+        writer.varint(*permits as u64);
+    }
+
+    writer.varint(task.steps().len() as u64);
+    task.steps().iter().for_each(|step| writer.step(step));
+
+    let crc = crc32(&writer.bytes);
+    writer.bytes.extend_from_slice(&crc.to_le_bytes());
+    writer.bytes
+}
+
+/// Appends the parts of a task to an image.
+#[derive(Debug, Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    /// Write an unsigned number in LEB128, seven bits to a byte.
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.byte(value as u8);
+    }
+
+    /// Write a signed number, zigzagged so that small negative numbers stay small.
+    fn signed(&mut self, value: i32) {
+        self.varint(((value << 1) ^ (value >> 31)) as u32 as u64);
+    }
+
+    fn string(&mut self, value: &str) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// Write a register as its index, with the top bit set for boolean registers.
+    fn register(&mut self, register: Register) {
+        self.byte(match register {
+            Register::Int(index) => index,
+            Register::Bool(index) => index | 0x80,
+        });
+    }
+
+    /// Write an optional register, where a byte of 0xFF means that there is none.
+    fn optional_register(&mut self, register: Option<Register>) {
+        match register {
+            Some(register) => self.register(register),
+            None => self.byte(0xFF),
+        }
+    }
+
+    fn operand(&mut self, operand: Operand) {
+        match operand {
+            Operand::Register(register) => {
+                self.byte(0);
+                self.register(register);
+            }
+            Operand::Immediate(Value::Int(value)) => {
+                self.byte(1);
+                self.signed(value);
+            }
+            Operand::Immediate(Value::Bool(value)) => {
+                self.byte(2);
+                self.byte(value as u8);
+            }
+        }
+    }
+
+    /// Write the configuration of a pin packed into a single byte.
+    fn pin_config(&mut self, config: &PinConfig) {
+        let pull = match config.pull() {
+            PullMode::Floating => 0,
+            PullMode::Up => 1,
+            PullMode::Down => 2,
+            PullMode::UpDown => 3,
+        };
+        let drive = match config.drive() {
+            None => 0,
+            Some(DriveCapability::I5mA) => 1,
+            Some(DriveCapability::I10mA) => 2,
+            Some(DriveCapability::I20mA) => 3,
+            Some(DriveCapability::I40mA) => 4,
+        };
+        let initial = match config.initial_level() {
+            None => 0,
+            Some(Level::Low) => 1,
+            Some(Level::High) => 2,
+        };
+        self.byte(pull | (*config.open_drain() as u8) << 2 | drive << 3 | initial << 6);
+    }
+
+    fn level(&mut self, level: Level) {
+        self.byte(bool::from(level) as u8);
+    }
+
+    /// Write an optional timeout, where zero means that there is none.
+    fn timeout(&mut self, timeout: Option<u32>) {
+        self.varint(timeout.map_or(0, |ms| ms as u64 + 1));
+    }
+
+    fn step(&mut self, step: &TaskStep) {
+        self.byte(Opcode::of(step) as u8);
+
+        match step {
+            TaskStep::ReadGPIO(pin, register) => {
+                self.signed(*pin);
+                self.register(*register);
+            }
+            TaskStep::WriteGPIO(pin, level) => {
+                self.signed(*pin);
+                self.level(*level);
+            }
+            TaskStep::Yield(ms) => self.varint(*ms as u64),
+            TaskStep::YieldNow | TaskStep::MarkTime | TaskStep::Return => (),
+            TaskStep::SleepUntil(ms, anchor) => {
+                self.varint(*ms as u64);
+                self.byte(match anchor {
+                    TimeAnchor::Start => 0,
+                    TimeAnchor::Mark => 1,
+                });
+            }
+            TaskStep::WaitLevel(pin, level, timeout, register) => {
+                self.signed(*pin);
+                self.level(*level);
+                self.timeout(*timeout);
+                self.optional_register(*register);
+            }
+            TaskStep::WaitEdge(pin, edge, timeout, register) => {
+                self.signed(*pin);
+                self.byte(match edge {
+                    Edge::Rising => 0,
+                    Edge::Falling => 1,
+                    Edge::Any => 2,
+                });
+                self.timeout(*timeout);
+                self.optional_register(*register);
+            }
+            TaskStep::Send(channel, value) => {
+                self.string(channel);
+                self.signed(*value);
+            }
+            TaskStep::Recv(channel, register) => {
+                self.string(channel);
+                self.register(*register);
+            }
+            TaskStep::Store(variable, register) => {
+                self.string(variable);
+                self.register(*register);
+            }
+            TaskStep::Load(register, variable) => {
+                self.register(*register);
+                self.string(variable);
+            }
+            TaskStep::Set(register, operand)
+            | TaskStep::Add(register, operand)
+            | TaskStep::Sub(register, operand) => {
+                self.register(*register);
+                self.operand(*operand);
+            }
+            TaskStep::Compare(result, register, operand) => {
+                self.register(*result);
+                self.register(*register);
+                self.operand(*operand);
+            }
+            TaskStep::Jump(target) => self.varint(*target as u64),
+            TaskStep::JumpIf(register, condition, target) => {
+                self.register(*register);
+                self.byte(match condition {
+                    Condition::Equal => 0,
+                    Condition::NotEqual => 1,
+                    Condition::Less => 2,
+                    Condition::LessOrEqual => 3,
+                    Condition::Greater => 4,
+                    Condition::GreaterOrEqual => 5,
+                });
+                self.varint(*target as u64);
+            }
+            TaskStep::Loop(register, target) => {
+                self.register(*register);
+                self.varint(*target as u64);
+            }
+            TaskStep::Call(name) | TaskStep::Acquire(name) | TaskStep::Release(name) => {
+                self.string(name);
+            }
+            TaskStep::ReadGPIOIndirect(pin, register) => {
+                self.register(*pin);
+                self.register(*register);
+            }
+            TaskStep::WriteGPIOIndirect(pin, level) => {
+                self.register(*pin);
+                self.level(*level);
+            }
+            TaskStep::YieldIndirect(register) => self.register(*register),
+            TaskStep::SetBits(group, mask) | TaskStep::ClearBits(group, mask) => {
+                self.string(group);
+                self.varint(*mask as u64);
+            }
+            TaskStep::WaitBits(group, mask, mode, clear) => {
+                self.string(group);
+                self.varint(*mask as u64);
+                self.byte(match mode {
+                    WaitMode::All => 0,
+                    WaitMode::Any => 1,
+                });
+                self.byte(*clear as u8);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use esp_idf_hal::gpio::Level;
+
+    use super::encode;
+    use crate::{
+        binary::{decode, Opcode},
+        task::{
+            tests::{assert_same, described_task, every_step},
+            Shot, Task, TaskPriority, TaskStep,
+        },
+        testing,
+    };
+
+    testing::tests!(
+        encodes_golden_image,
+        covers_every_opcode,
+        round_trips_every_step,
+        round_trips_limits
+    );
+
+    /// The image of the task in the doc comment of [`encode`].
+    pub const GOLDEN: [u8; 35] = [
+        0x54, 0x41, 0x53, 0x4b, 0x05, // magic, version
+        0x05, 0x42, 0x6c, 0x69, 0x6e, 0x6b, 0x40, 0x00, // name, priority, shots
+        0x01, 0x04, 0x00, 0x00, // pins
+        0x00, // claims
+        0x04, // step count
+        0x01, 0x04, 0x01, // high 2
+        0x02, 0xe8, 0x07, // wait 1000ms
+        0x01, 0x04, 0x00, // low 2
+        0x02, 0xe8, 0x07, // wait 1000ms
+        0x1e, 0x7c, 0x39, 0x21, // crc
+    ];
+
+    pub fn golden_task() -> Task {
+        Task::new(
+            "Blink",
+            TaskPriority::Low,
+            Shot::Infinity,
+            vec![
+                TaskStep::WriteGPIO(2, Level::High),
+                TaskStep::Yield(1000),
+                TaskStep::WriteGPIO(2, Level::Low),
+                TaskStep::Yield(1000),
+            ],
+        )
+    }
+
+    fn encodes_golden_image() -> anyhow::Result<()> {
+        assert_eq!(encode(&golden_task()), GOLDEN);
+        assert_same(&decode(&GOLDEN)?, &golden_task());
+        Ok(())
+    }
+
+    fn covers_every_opcode() -> anyhow::Result<()> {
+        let mut opcodes: Vec<_> = every_step("bus").iter().map(Opcode::of).collect();
+        opcodes.dedup();
+        assert_eq!(opcodes, Opcode::ALL);
+        Ok(())
+    }
+
+    fn round_trip(task: &Task) -> anyhow::Result<()> {
+        assert_same(&decode(&encode(task))?, task);
+        Ok(())
+    }
+
+    fn round_trips_every_step() -> anyhow::Result<()> {
+        round_trip(&described_task("Every Step", every_step("bus"))?)
+    }
+
+    fn round_trips_limits() -> anyhow::Result<()> {
+        // Long enough that its length takes more than one byte to write.
+        let long = "Ünïcödé ".repeat(100);
+        round_trip(&described_task(&long, every_step(&long))?)?;
+        round_trip(&described_task("", every_step(""))?)?;
+
+        for shots in [
+            Shot::Infinity,
+            Shot::Custom(0),
+            Shot::Custom(u32::MAX as usize),
+        ] {
+            let mut task = described_task("Shots", vec![])?;
+            task.set_shots(shots);
+            round_trip(&task)?;
+        }
+        for priority in [
+            TaskPriority::Normal,
+            TaskPriority::High,
+            TaskPriority::Custom(0),
+        ] {
+            let mut task = described_task("Priority", vec![])?;
+            task.context_mut().set_priority(priority);
+            round_trip(&task)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+mod crc;
+mod decoder;
+mod encoder;
+mod opcode;
+
+pub use decoder::decode;
+pub use encoder::encode;
+pub use opcode::Opcode;
+
+/// The tests of every module in here.
+#[cfg(test)]
+pub const TESTS: &[&[crate::testing::Test]] = &[encoder::tests::TESTS, decoder::tests::TESTS];
+
+/// The bytes that every binary image of a task starts with.
+pub const MAGIC: [u8; 4] = *b"TASK";
+
+/// The version of the binary format written by this build.
+///
+/// An image is laid out as the magic header and this version, then the name,
+/// priority, shots, pins, maximum claims and steps of the task, and finally a little endian
+/// CRC-32 of everything before it. Numbers are LEB128 varints, with signed ones
+/// zigzagged first, and text is its length followed by its UTF-8 bytes. Each
+/// step is its [`Opcode`] followed by its values in order, where a register is
+/// a byte of its index with the top bit set for boolean registers, and a missing
+/// register is a byte of 0xFF. Each pin is followed
+/// by a byte for how it is held, zero for output, one for shared and two for input,
+/// and a byte for how it is configured. The lowest two bits of that byte are the
+/// pull mode, in the order floating, up, down and both, the next bit is set for
+/// open-drain, the next three bits are the drive capability, zero for the default
+/// and then 5, 10, 20 and 40 mA, and the top two bits are the initial level, zero
+/// for none, one for low and two for high. Each claim is the name of the semaphore
+/// followed by the number of permits.
+pub const VERSION: u8 = 5;
@@ -0,0 +1,122 @@
+use anyhow::Context;
+
+use crate::task::TaskStep;
+
+/// Identifies each kind of step in a binary image.
+///
+/// The values are part of the format, so they must never change once released.
+/// New steps are given the next free value, and bump the format version.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+    ReadGPIO = 0x00,
+    WriteGPIO = 0x01,
+    Yield = 0x02,
+    YieldNow = 0x03,
+    SleepUntil = 0x04,
+    MarkTime = 0x05,
+    WaitLevel = 0x06,
+    WaitEdge = 0x07,
+    Send = 0x08,
+    Recv = 0x09,
+    Store = 0x0A,
+    Load = 0x0B,
+    Set = 0x0C,
+    Add = 0x0D,
+    Sub = 0x0E,
+    Compare = 0x0F,
+    Jump = 0x10,
+    JumpIf = 0x11,
+    Loop = 0x12,
+    Call = 0x13,
+    Return = 0x14,
+    ReadGPIOIndirect = 0x15,
+    WriteGPIOIndirect = 0x16,
+    YieldIndirect = 0x17,
+    Acquire = 0x18,
+    Release = 0x19,
+    SetBits = 0x1A,
+    ClearBits = 0x1B,
+    WaitBits = 0x1C,
+}
+
+impl Opcode {
+    /// Every opcode, in order of their values.
+    pub const ALL: [Opcode; 29] = [
+        Self::ReadGPIO,
+        Self::WriteGPIO,
+        Self::Yield,
+        Self::YieldNow,
+        Self::SleepUntil,
+        Self::MarkTime,
+        Self::WaitLevel,
+        Self::WaitEdge,
+        Self::Send,
+        Self::Recv,
+        Self::Store,
+        Self::Load,
+        Self::Set,
+        Self::Add,
+        Self::Sub,
+        Self::Compare,
+        Self::Jump,
+        Self::JumpIf,
+        Self::Loop,
+        Self::Call,
+        Self::Return,
+        Self::ReadGPIOIndirect,
+        Self::WriteGPIOIndirect,
+        Self::YieldIndirect,
+        Self::Acquire,
+        Self::Release,
+        Self::SetBits,
+        Self::ClearBits,
+        Self::WaitBits,
+    ];
+
+    /// The opcode that a step is encoded with.
+    pub fn of(step: &TaskStep) -> Self {
+        match step {
+            TaskStep::ReadGPIO(..) => Self::ReadGPIO,
+            TaskStep::WriteGPIO(..) => Self::WriteGPIO,
+            TaskStep::Yield(..) => Self::Yield,
+            TaskStep::YieldNow => Self::YieldNow,
+            TaskStep::SleepUntil(..) => Self::SleepUntil,
+            TaskStep::MarkTime => Self::MarkTime,
+            TaskStep::WaitLevel(..) => Self::WaitLevel,
+            TaskStep::WaitEdge(..) => Self::WaitEdge,
+            TaskStep::Send(..) => Self::Send,
+            TaskStep::Recv(..) => Self::Recv,
+            TaskStep::Store(..) => Self::Store,
+            TaskStep::Load(..) => Self::Load,
+            TaskStep::Set(..) => Self::Set,
+            TaskStep::Add(..) => Self::Add,
+            TaskStep::Sub(..) => Self::Sub,
+            TaskStep::Compare(..) => Self::Compare,
+            TaskStep::Jump(..) => Self::Jump,
+            TaskStep::JumpIf(..) => Self::JumpIf,
+            TaskStep::Loop(..) => Self::Loop,
+            TaskStep::Call(..) => Self::Call,
+            TaskStep::Return => Self::Return,
+            TaskStep::ReadGPIOIndirect(..) => Self::ReadGPIOIndirect,
+            TaskStep::WriteGPIOIndirect(..) => Self::WriteGPIOIndirect,
+            TaskStep::YieldIndirect(..) => Self::YieldIndirect,
+            TaskStep::Acquire(..) => Self::Acquire,
+            TaskStep::Release(..) => Self::Release,
+            TaskStep::SetBits(..) => Self::SetBits,
+            TaskStep::ClearBits(..) => Self::ClearBits,
+            TaskStep::WaitBits(..) => Self::WaitBits,
+        }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> anyhow::Result<Self> {
+        Self::ALL
+            .get(value as usize)
+            .copied()
+            .context(format!("Unknown opcode {value:#04x}!"))
+    }
+}
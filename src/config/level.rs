@@ -0,0 +1,13 @@
+use esp_idf_hal::gpio::Level;
+use serde::{Deserialize, Serialize};
+
+/// Mirrors the level of a pin from esp-idf-hal, which does not implement serde itself.
+///
+/// Levels are written as `"low"` or `"high"`. Use it on a field holding a level
+/// with `#[serde(with = "crate::config::LevelDef")]`.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Level", rename_all = "snake_case")]
+pub enum LevelDef {
+    Low,
+    High,
+}
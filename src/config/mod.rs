@@ -0,0 +1,13 @@
+mod level;
+pub mod optional_level;
+pub mod pin_table;
+pub mod result_register;
+pub mod timeout;
+mod workload;
+
+pub use level::LevelDef;
+pub use workload::Workload;
+
+/// The tests of every module in here.
+#[cfg(test)]
+pub const TESTS: &[&[crate::testing::Test]] = &[workload::tests::TESTS];
@@ -0,0 +1,22 @@
+//! Writes an optional level as `"low"` or `"high"`, as [`LevelDef`] does for a
+//! level that is always there.
+//!
+//! Use it on a field holding an optional level with
+//! `#[serde(with = "crate::config::optional_level")]`, skipping the field when
+//! there is no level, as TOML has no null value.
+
+use esp_idf_hal::gpio::Level;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::LevelDef;
+
+#[derive(Serialize, Deserialize)]
+struct Wrapper(#[serde(with = "LevelDef")] Level);
+
+pub fn serialize<S: Serializer>(level: &Option<Level>, serializer: S) -> Result<S::Ok, S::Error> {
+    level.map(Wrapper).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Level>, D::Error> {
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(level)| level))
+}
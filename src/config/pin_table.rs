@@ -0,0 +1,33 @@
+//! Writes a map holding something for each pin, such as its access mode or its
+//! configuration, as a table keyed by the pin number as text.
+//!
+//! TOML only allows text as the keys of a table, so pin numbers can't be used
+//! as keys directly. Use it on a field holding a map of pins with
+//! `#[serde(with = "crate::config::pin_table")]`.
+
+use std::collections::BTreeMap;
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S: Serializer, V: Serialize>(
+    pins: &BTreeMap<i32, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    pins.iter()
+        .map(|(pin, value)| (pin.to_string(), value))
+        .collect::<BTreeMap<_, _>>()
+        .serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<i32, V>, D::Error> {
+    BTreeMap::<String, V>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(pin, value)| {
+            pin.parse()
+                .map(|pin| (pin, value))
+                .map_err(|_| D::Error::custom(format!("'{pin}' is not a pin number")))
+        })
+        .collect()
+}
@@ -0,0 +1,43 @@
+//! Writes the optional register that a wait stores its result in, as the register
+//! or as `"none"`.
+//!
+//! TOML has no null value, and the register is the last value of a step rather
+//! than a field that could be skipped, so a missing register needs a value of its
+//! own. Use it with `#[serde(with = "crate::config::result_register")]`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::task::Register;
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ResultRegister {
+    Register(Register),
+    None(NoRegister),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NoRegister {
+    None,
+}
+
+pub fn serialize<S: Serializer>(
+    register: &Option<Register>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match register {
+        Some(register) => ResultRegister::Register(*register),
+        None => ResultRegister::None(NoRegister::None),
+    }
+    .serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Register>, D::Error> {
+    Ok(match ResultRegister::deserialize(deserializer)? {
+        ResultRegister::Register(register) => Some(register),
+        ResultRegister::None(_) => None,
+    })
+}
@@ -0,0 +1,34 @@
+//! Writes an optional timeout as a number of milliseconds, or as `"never"`.
+//!
+//! TOML has no null value, so a missing timeout needs a value of its own. Use it
+//! on a field holding a timeout with `#[serde(with = "crate::config::timeout")]`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Timeout {
+    Millis(u32),
+    Never(Never),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Never {
+    Never,
+}
+
+pub fn serialize<S: Serializer>(timeout: &Option<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+    match timeout {
+        Some(ms) => Timeout::Millis(*ms),
+        None => Timeout::Never(Never::Never),
+    }
+    .serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Ok(match Timeout::deserialize(deserializer)? {
+        Timeout::Millis(ms) => Some(ms),
+        Timeout::Never(_) => None,
+    })
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use getset::{Getters, MutGetters};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scheduler::TaskScheduler,
+    task::{Task, TaskStep},
+};
+
+/// Everything that a scheduler runs, so that it can be kept in a config file.
+///
+/// The schema is versioned, and a workload is only loaded if its version matches
+/// [`Workload::VERSION`]. Every name is written in snake case, as is every step,
+/// with the values of a step given in the same order as in [`TaskStep`]. Levels
+/// are `"low"` or `"high"`, timeouts are milliseconds or `"never"`, registers
+/// are `{ int = 0 }` or `{ bool = 0 }`, or `"none"` for a wait that keeps no
+/// result, and operands are `{ register = ... }` or `{ immediate = { int = 5 } }`.
+/// Only the name, id, priority and pins of a task context are kept, and the id is
+/// generated when left out. Pins used directly by the steps are declared on
+/// loading, so they can be left out of `pins_used`, unless the task sets
+/// `infer_pins = false`. Pins are held as outputs unless `pin_modes` says
+/// otherwise, as in
+/// `pin_modes = { 4 = "input", 5 = "shared" }`, and are configured as the chip
+/// resets them unless `pin_configs` says otherwise, as in
+/// `pin_configs = { 4 = { pull = "up" }, 5 = { open_drain = true, drive = "10mA", initial_level = "high" } }`.
+/// The most permits of each semaphore that a task holds at once are given as
+/// `max_claims = { pwm = 2 }`.
+///
+/// ```toml
+/// version = 1
+///
+/// [channels]
+/// readings = 4
+///
+/// [[tasks]]
+/// shots = "infinity"
+/// steps = [
+///     { write_gpio = [2, "high"] },
+///     { yield = 1000 },
+///     { write_gpio = [2, "low"] },
+///     { yield = 1000 },
+/// ]
+///
+/// [tasks.context]
+/// name = "Blink Blue LED"
+/// priority = "low"
+/// pins_used = [2]
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Getters, MutGetters, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workload {
+    /// The version of the schema that the workload is written in.
+    #[getset(get = "pub")]
+    version: u32,
+    /// The tasks to schedule.
+    #[getset(get = "pub", get_mut = "pub")]
+    #[serde(default)]
+    tasks: Vec<Task>,
+    /// The routines that tasks can call, by name.
+    #[getset(get = "pub", get_mut = "pub")]
+    #[serde(default)]
+    routines: BTreeMap<String, Vec<TaskStep>>,
+    /// The channels to create, by name, along with their capacity.
+    #[getset(get = "pub", get_mut = "pub")]
+    #[serde(default)]
+    channels: BTreeMap<String, usize>,
+    /// The counting semaphores to create, by name, along with their permits.
+    #[getset(get = "pub", get_mut = "pub")]
+    #[serde(default)]
+    semaphores: BTreeMap<String, usize>,
+    /// The names of the mutexes to create.
+    #[getset(get = "pub", get_mut = "pub")]
+    #[serde(default)]
+    mutexes: Vec<String>,
+    /// The names of the event groups to create.
+    #[getset(get = "pub", get_mut = "pub")]
+    #[serde(default)]
+    event_groups: Vec<String>,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            tasks: vec![],
+            routines: BTreeMap::new(),
+            channels: BTreeMap::new(),
+            semaphores: BTreeMap::new(),
+            mutexes: vec![],
+            event_groups: vec![],
+        }
+    }
+}
+
+impl Workload {
+    /// The version of the schema written by this build.
+    pub const VERSION: u32 = 1;
+
+    /// Create a new empty workload in the current version of the schema.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a workload from JSON.
+    pub fn from_json(source: &str) -> anyhow::Result<Self> {
+        serde_json::from_str::<Self>(source)
+            .context("Could not parse workload from JSON!")?
+            .checked()
+    }
+
+    /// Save the workload as JSON.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        serde_json::to_string_pretty(self).context("Could not write workload as JSON!")
+    }
+
+    /// Load a workload from TOML.
+    pub fn from_toml(source: &str) -> anyhow::Result<Self> {
+        toml::from_str::<Self>(source)
+            .context("Could not parse workload from TOML!")?
+            .checked()
+    }
+
+    /// Save the workload as TOML.
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        toml::to_string_pretty(self).context("Could not write workload as TOML!")
+    }
+
+    /// Create every resource in the workload on the scheduler, then schedule its tasks.
+    pub fn apply(self, scheduler: &mut TaskScheduler) -> anyhow::Result<()> {
+        for (name, steps) in self.routines {
+            scheduler.register_routine(name, steps)?;
+        }
+        for (name, capacity) in self.channels {
+            scheduler.create_channel(name, capacity)?;
+        }
+        for (name, permits) in self.semaphores {
+            scheduler.create_semaphore(name, permits)?;
+        }
+        for name in self.mutexes {
+            scheduler.create_mutex(name)?;
+        }
+        for name in self.event_groups {
+            scheduler.create_event_group(name)?;
+        }
+
+        scheduler.schedule_bulk(self.tasks)
+    }
+
+    /// Check that the workload is in a version that can be loaded, then finish
+    /// its tasks just as if they had been built.
+    fn checked(mut self) -> anyhow::Result<Self> {
+        (self.version == Self::VERSION)
+            .then_some(())
+            .context(format!(
+                "Workload version {} is not supported, expected version {}!",
+                self.version,
+                Self::VERSION
+            ))?;
+
+        self.tasks = self
+            .tasks
+            .into_iter()
+            .map(|task| {
+                let name = task.context().name().clone();
+                anyhow::Result::from(task).context(format!("Task {name} is not valid!"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::BTreeMap;
+
+    use anyhow::Context;
+    use esp_idf_hal::gpio::Level;
+
+    use super::Workload;
+    use crate::{
+        task::{
+            tests::{assert_same, described_task, every_step},
+            Shot, Task, TaskContext, TaskPriority, TaskStep,
+        },
+        testing,
+    };
+
+    testing::tests!(
+        loads_documented_example,
+        round_trips_json,
+        round_trips_toml,
+        rejects_other_versions,
+        rejects_unknown_fields,
+    );
+
+    /// The example from the documentation of `Workload`.
+    const EXAMPLE: &str = r#"
+version = 1
+
+[channels]
+readings = 4
+
+[[tasks]]
+shots = "infinity"
+steps = [
+    { write_gpio = [2, "high"] },
+    { yield = 1000 },
+    { write_gpio = [2, "low"] },
+    { yield = 1000 },
+]
+
+[tasks.context]
+name = "Blink Blue LED"
+priority = "low"
+pins_used = [2]
+"#;
+
+    /// A workload with a task that uses every step, and one that declares its pins by hand.
+    fn workload() -> anyhow::Result<Workload> {
+        let manual = Task::builder()
+            .context(
+                TaskContext::builder()
+                    .name("Manual")
+                    .pins_used(vec![4])
+                    .build(),
+            )
+            .steps(vec![TaskStep::WriteGPIO(4, Level::High)])
+            .shots(Shot::Custom(1))
+            .infer_pins(false)
+            .build()?;
+
+        let mut workload = Workload::new();
+        *workload.tasks_mut() = vec![described_task("pwm", every_step("pwm"))?, manual];
+        workload.semaphores_mut().insert("pwm".into(), 2);
+        Ok(workload)
+    }
+
+    /// Check that a workload read back is the same as the one that was written.
+    fn assert_round_trip(workload: &Workload, read: &Workload) {
+        assert_eq!(read.tasks().len(), workload.tasks().len());
+        for (left, right) in workload.tasks().iter().zip(read.tasks()) {
+            assert_same(left, right);
+            assert_eq!(left.infer_pins(), right.infer_pins());
+        }
+        assert_eq!(read.semaphores(), workload.semaphores());
+    }
+
+    fn loads_documented_example() -> anyhow::Result<()> {
+        let workload = Workload::from_toml(EXAMPLE)?;
+        let expected = Task::builder()
+            .context(
+                TaskContext::builder()
+                    .name("Blink Blue LED")
+                    .priority(TaskPriority::Low)
+                    .pins_used(vec![2])
+                    .build(),
+            )
+            .steps(vec![
+                TaskStep::WriteGPIO(2, Level::High),
+                TaskStep::Yield(1000),
+                TaskStep::WriteGPIO(2, Level::Low),
+                TaskStep::Yield(1000),
+            ])
+            .shots(Shot::Infinity)
+            .build()?;
+
+        let [task] = workload.tasks().as_slice() else {
+            anyhow::bail!("Expected a single task, found {}!", workload.tasks().len());
+        };
+        assert_same(task, &expected);
+        assert_eq!(
+            workload.channels(),
+            &BTreeMap::from([("readings".to_string(), 4)])
+        );
+        Ok(())
+    }
+
+    fn round_trips_json() -> anyhow::Result<()> {
+        let workload = workload()?;
+        let read = Workload::from_json(&workload.to_json()?)?;
+        assert_round_trip(&workload, &read);
+        Ok(())
+    }
+
+    fn round_trips_toml() -> anyhow::Result<()> {
+        let workload = workload()?;
+        let read = Workload::from_toml(&workload.to_toml()?)?;
+        assert_round_trip(&workload, &read);
+        Ok(())
+    }
+
+    fn rejects_other_versions() -> anyhow::Result<()> {
+        let error = Workload::from_toml(&EXAMPLE.replace("version = 1", "version = 2"))
+            .err()
+            .context("A workload in another version should be rejected!")?;
+        assert!(error.to_string().contains("version 2 is not supported"));
+        Ok(())
+    }
+
+    fn rejects_unknown_fields() -> anyhow::Result<()> {
+        let source = EXAMPLE.replace("version = 1", "version = 1\ntimers = 2");
+        assert!(Workload::from_toml(&source).is_err());
+        assert!(Workload::from_json(r#"{ "version": 1, "timers": 2 }"#).is_err());
+        Ok(())
+    }
+}
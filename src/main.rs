@@ -1,57 +1,64 @@
 #![feature(map_many_mut, extract_if)]
 
 use anyhow::Context;
-use esp_idf_hal::gpio::*;
 
+mod asm;
+#[cfg(feature = "bench")]
+mod bench;
+mod binary;
+#[cfg(feature = "serde")]
+mod config;
 mod resource;
 mod scheduler;
 mod task;
+mod testing;
 
-use resource::TaskResource;
 use scheduler::TaskScheduler;
-use task::{Shot, Task, TaskContext, TaskPriority, TaskStep};
 
 fn main() -> anyhow::Result<()> {
     esp_idf_svc::sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
+    #[cfg(feature = "bench")]
+    if !cfg!(test) {
+        return bench::gpio();
+    }
+
+    if cfg!(test) {
+        testing::run()
+    } else {
+        run()
+    }
+}
+
+/// Schedule the example tasks and run them forever.
+fn run() -> anyhow::Result<()> {
     let mut scheduler = TaskScheduler::new().context("Could not start task scheduler!")?;
+    scheduler.set_lint(true);
+
+    let blink_always = util::task! {
+        name: "Blink Blue LED",
+        priority: Low,
+        shots: Infinity,
+        pins: [2],
+        steps: { high 2; wait 1s; low 2; wait 1s; }
+    }?;
+
+    let blink_five_times = asm::assemble(
+        r#"
+        .name "Blink Green LED"
+        .priority low
+        .shots 5
+        .pins 4
+
+            wait 1000ms
+            high 4
+            wait 1000ms
+            low 4
+        "#,
+    )?;
+
+    scheduler.schedule_bulk(vec![blink_always, blink_five_times])?;
 
-    let blink_always = Task::builder()
-        .shots(Shot::Infinity)
-        .context(
-            TaskContext::builder()
-                .name("Blink Blue LED")
-                .priority(TaskPriority::Low)
-                .build(),
-        )
-        .steps(vec![
-            TaskStep::WriteGPIO(2, Level::High),
-            TaskStep::Yield(1000),
-            TaskStep::WriteGPIO(2, Level::Low),
-            TaskStep::Yield(1000),
-        ])
-        .build()
-        .assign(TaskResource::Pin(2));
-
-    let blink_five_times = Task::builder()
-        .shots(Shot::Custom(5))
-        .context(
-            TaskContext::builder()
-                .name("Blink Green LED")
-                .priority(TaskPriority::Low)
-                .build(),
-        )
-        .steps(vec![
-            TaskStep::Yield(1000),
-            TaskStep::WriteGPIO(4, Level::High),
-            TaskStep::Yield(1000),
-            TaskStep::WriteGPIO(4, Level::Low),
-        ])
-        .build()
-        .assign(TaskResource::Pin(4));
-
-    scheduler.schedule_bulk(vec![blink_always, blink_five_times]);
-
-    scheduler.run()
+    scheduler.start()
 }
@@ -0,0 +1,64 @@
+use std::fmt;
+
+/// Represents how a task holds a resource that it has declared.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum AccessMode {
+    /// The task only ever reads the resource, so any number of other tasks that
+    /// only read it may hold it too.
+    Input,
+    /// Only this task may hold the resource, and it may both read and write it.
+    #[default]
+    Output,
+    /// Any number of tasks that also declare the resource as shared may hold it,
+    /// and are trusted to take turns writing to it.
+    Shared,
+}
+
+impl AccessMode {
+    /// Whether a task holding a resource in this mode can hold it alongside a
+    /// task holding it in the other mode.
+    pub fn compatible(self, other: AccessMode) -> bool {
+        matches!(
+            (self, other),
+            (Self::Input, Self::Input) | (Self::Shared, Self::Shared)
+        )
+    }
+
+    /// Whether a task holding a resource in this mode may use it in the given way.
+    pub fn allows(self, access: Access) -> bool {
+        access == Access::Read || self != Self::Input
+    }
+}
+
+impl fmt::Display for AccessMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Input => "input",
+            Self::Output => "output",
+            Self::Shared => "shared",
+        })
+    }
+}
+
+/// Represents a single use of a resource by a step.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Access {
+    /// The step only looks at the resource, such as reading the level of a pin.
+    Read,
+    /// The step changes the resource, such as setting the level of a pin.
+    Write,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        })
+    }
+}
@@ -0,0 +1,228 @@
+use anyhow::Context;
+use getset::Getters;
+
+/// The state of a system of processes sharing several classes of resources, as
+/// used by the Banker's algorithm to avoid deadlock.
+///
+/// The names follow Stallings: the resource vector `R` holds how many units of
+/// each class exist, the claim matrix `C` holds the most that each process may
+/// ever hold at once, and the allocation matrix `A` holds what each process
+/// holds now. A state is safe when there is some order in which every process
+/// can be given the rest of its claim, run to completion, and give everything
+/// back. Only allocations that lead to a safe state are ever granted.
+///
+/// The tests follow the examples of figures 6.7 and 6.8. In the first, P2 can
+/// finish first, followed by P1, P3 and P4. In the second, granting P1 one more
+/// unit of R1 and R3 would leave no process able to finish, so it is refused.
+#[derive(Debug, PartialEq, Eq, Clone, Getters)]
+pub struct BankerState {
+    /// The total units of each resource class, `R`.
+    #[getset(get = "pub")]
+    resources: Vec<usize>,
+    /// The most units of each class that each process may hold at once, `C`.
+    #[getset(get = "pub")]
+    claim: Vec<Vec<usize>>,
+    /// The units of each class that each process holds right now, `A`.
+    #[getset(get = "pub")]
+    allocation: Vec<Vec<usize>>,
+}
+
+impl BankerState {
+    /// Create a new state, checking that it is one that can actually happen.
+    ///
+    /// Every row of the matrices must have a column for each class of resource,
+    /// no process may claim more than exists or hold more than it claims, and no
+    /// more can be held than exists.
+    pub fn new(
+        resources: Vec<usize>,
+        claim: Vec<Vec<usize>>,
+        allocation: Vec<Vec<usize>>,
+    ) -> anyhow::Result<Self> {
+        (claim.len() == allocation.len())
+            .then_some(())
+            .context(format!(
+                "There are claims for {} processes, but allocations for {}!",
+                claim.len(),
+                allocation.len()
+            ))?;
+
+        for (process, (claimed, allocated)) in claim.iter().zip(&allocation).enumerate() {
+            (claimed.len() == resources.len() && allocated.len() == resources.len())
+                .then_some(())
+                .context(format!(
+                    "Process {process} does not have a claim and allocation for each of the {} resources!",
+                    resources.len()
+                ))?;
+
+            for (resource, total) in resources.iter().enumerate() {
+                (claimed[resource] <= *total)
+                    .then_some(())
+                    .context(format!(
+                    "Process {process} claims {} of resource {resource}, but only {total} exist!",
+                    claimed[resource]
+                ))?;
+                (allocated[resource] <= claimed[resource])
+                    .then_some(())
+                    .context(format!(
+                        "Process {process} holds {} of resource {resource}, which is more than its claim of {}!",
+                        allocated[resource], claimed[resource]
+                    ))?;
+            }
+        }
+
+        let state = Self {
+            resources,
+            claim,
+            allocation,
+        };
+        for (resource, total) in state.resources.iter().enumerate() {
+            let held: usize = state.allocation.iter().map(|row| row[resource]).sum();
+            (held <= *total).then_some(()).context(format!(
+                "{held} units of resource {resource} are held, but only {total} exist!"
+            ))?;
+        }
+
+        Ok(state)
+    }
+
+    /// The units of each class that nobody holds, `V`.
+    pub fn available(&self) -> Vec<usize> {
+        self.resources
+            .iter()
+            .enumerate()
+            .map(|(resource, total)| {
+                total
+                    - self
+                        .allocation
+                        .iter()
+                        .map(|row| row[resource])
+                        .sum::<usize>()
+            })
+            .collect()
+    }
+
+    /// The units of each class that a process may still ask for, `C - A`.
+    pub fn need(&self, process: usize) -> Vec<usize> {
+        self.claim[process]
+            .iter()
+            .zip(&self.allocation[process])
+            .map(|(claimed, allocated)| claimed - allocated)
+            .collect()
+    }
+
+    /// An order in which every process can finish, if the state is safe.
+    ///
+    /// Whenever several processes could finish next, the first of them is chosen.
+    pub fn safe_sequence(&self) -> Option<Vec<usize>> {
+        let mut available = self.available();
+        let mut finished = vec![false; self.claim.len()];
+        let mut sequence = Vec::with_capacity(self.claim.len());
+
+        while sequence.len() < self.claim.len() {
+            let process = (0..self.claim.len()).find(|process| {
+                !finished[*process]
+                    && self
+                        .need(*process)
+                        .iter()
+                        .zip(&available)
+                        .all(|(needed, free)| needed <= free)
+            })?;
+
+            // The process runs to completion, and gives back everything it holds.
+            for (free, allocated) in available.iter_mut().zip(&self.allocation[process]) {
+                *free += allocated;
+            }
+            finished[process] = true;
+            sequence.push(process);
+        }
+
+        Some(sequence)
+    }
+
+    /// Whether every process can still finish.
+    pub fn is_safe(&self) -> bool {
+        self.safe_sequence().is_some()
+    }
+
+    /// Whether the request of a process can be granted right now, leaving the
+    /// system in a safe state.
+    ///
+    /// Requests for more than the process has left of its claim, or for more than
+    /// is available, are never granted.
+    pub fn can_grant(&self, process: usize, request: &[usize]) -> bool {
+        let fits = request
+            .iter()
+            .zip(self.need(process))
+            .zip(self.available())
+            .all(|((requested, needed), free)| *requested <= needed && *requested <= free);
+
+        fits && {
+            let mut state = self.clone();
+            for (allocated, requested) in state.allocation[process].iter_mut().zip(request) {
+                *allocated += requested;
+            }
+            state.is_safe()
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::BankerState;
+    use crate::testing;
+
+    testing::tests!(figure_6_7, figure_6_8, rejects_impossible_states);
+
+    const RESOURCES: [usize; 3] = [9, 3, 6];
+
+    fn claim() -> Vec<Vec<usize>> {
+        vec![vec![3, 2, 2], vec![6, 1, 3], vec![3, 1, 4], vec![4, 2, 2]]
+    }
+
+    fn figure_6_7() -> anyhow::Result<()> {
+        let state = BankerState::new(
+            RESOURCES.to_vec(),
+            claim(),
+            vec![vec![1, 0, 0], vec![6, 1, 2], vec![2, 1, 1], vec![0, 0, 2]],
+        )?;
+        assert_eq!(state.available(), vec![0, 1, 1]);
+        assert_eq!(state.need(1), vec![0, 0, 1]);
+        assert_eq!(state.safe_sequence(), Some(vec![1, 0, 2, 3]));
+        Ok(())
+    }
+
+    fn figure_6_8() -> anyhow::Result<()> {
+        let state = BankerState::new(
+            RESOURCES.to_vec(),
+            claim(),
+            vec![vec![1, 0, 0], vec![5, 1, 1], vec![2, 1, 1], vec![0, 0, 2]],
+        )?;
+        assert_eq!(state.available(), vec![1, 1, 2]);
+        assert!(state.is_safe());
+        assert!(!state.can_grant(0, &[1, 0, 1]));
+        // The same request from P2 lets it finish straight away.
+        assert!(state.can_grant(1, &[1, 0, 1]));
+
+        // Granting it anyway gives the unsafe state of figure 6.8b.
+        let unsafe_state = BankerState::new(
+            RESOURCES.to_vec(),
+            claim(),
+            vec![vec![2, 0, 1], vec![5, 1, 1], vec![2, 1, 1], vec![0, 0, 2]],
+        )?;
+        assert_eq!(unsafe_state.available(), vec![0, 1, 1]);
+        assert_eq!(unsafe_state.safe_sequence(), None);
+        Ok(())
+    }
+
+    fn rejects_impossible_states() -> anyhow::Result<()> {
+        // More held than claimed.
+        assert!(BankerState::new(vec![2], vec![vec![1]], vec![vec![2]]).is_err());
+        // More claimed than exists.
+        assert!(BankerState::new(vec![2], vec![vec![3]], vec![vec![0]]).is_err());
+        // More held in total than exists.
+        assert!(BankerState::new(vec![2], vec![vec![2], vec![2]], vec![vec![2], vec![1]]).is_err());
+        // A missing column.
+        assert!(BankerState::new(vec![2, 2], vec![vec![1]], vec![vec![1]]).is_err());
+        Ok(())
+    }
+}
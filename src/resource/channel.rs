@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+
+/// A named bounded queue of values that tasks can send to and receive from.
+///
+/// Channels are owned by the resource manager and registered through the
+/// scheduler. A task that sends to a full channel, or receives from an empty
+/// one, is blocked until the channel changes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Channel {
+    /// The maximum number of values that can be waiting in the channel.
+    capacity: usize,
+    /// The values waiting to be received, oldest first.
+    queue: VecDeque<i32>,
+}
+
+impl Channel {
+    /// Create a new empty channel that can hold up to `capacity` values.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            queue: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Try to add a value to the back of the channel.
+    ///
+    /// Returns false without sending the value if the channel is full.
+    pub fn try_send(&mut self, value: i32) -> bool {
+        if self.queue.len() >= self.capacity {
+            return false;
+        }
+
+        self.queue.push_back(value);
+        true
+    }
+
+    /// Try to take the oldest value from the channel.
+    pub fn try_recv(&mut self) -> Option<i32> {
+        self.queue.pop_front()
+    }
+
+    /// The number of values waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether there are no values waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// The maximum number of values that can be waiting in the channel.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
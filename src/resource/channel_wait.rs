@@ -0,0 +1,149 @@
+use std::task::Poll;
+
+use super::{Request, RequestResolver, RequestResult, ResourceManager};
+use crate::{scheduler::waker, task::TaskContext};
+
+/// Resolves requests that send to or receive from a channel.
+///
+/// Completing either kind of request changes the channel, which may free a task
+/// waiting on the other end, so the scheduler is woken whenever one completes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChannelResolver;
+
+impl RequestResolver for ChannelResolver {
+    fn poll(
+        &mut self,
+        request: &Request,
+        _context: &TaskContext,
+        manager: &mut ResourceManager,
+    ) -> Poll<RequestResult> {
+        let result = match request {
+            Request::Send(name, value) => manager
+                .channel_mut(name)
+                .is_some_and(|channel| channel.try_send(*value))
+                .then_some(RequestResult::Done),
+            Request::Recv(name) => manager
+                .channel_mut(name)
+                .and_then(|channel| channel.try_recv())
+                .map(RequestResult::Value),
+            _ => None,
+        };
+
+        match result {
+            Some(result) => {
+                waker::wake();
+                Poll::Ready(result)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::task::Poll;
+
+    use anyhow::Context;
+
+    use super::ChannelResolver;
+    use crate::{
+        resource::{Request, RequestResolver, RequestResult, ResourceManager},
+        scheduler::waker,
+        task::{Register, TaskContext, TaskStep, Value},
+        testing,
+    };
+
+    testing::tests!(
+        blocks_sending_to_full_channels,
+        blocks_receiving_from_empty_channels
+    );
+
+    /// Run a step that uses the channel, returning the request that it blocks on.
+    fn request(
+        step: TaskStep,
+        context: &mut TaskContext,
+        manager: &mut ResourceManager,
+    ) -> anyhow::Result<Request> {
+        step.execute(context, manager)?
+            .context("Using a channel should make a request!")
+    }
+
+    fn blocks_sending_to_full_channels() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        manager.create_channel("readings".into(), 1)?;
+        let mut sender = TaskContext::builder().name("Sender").build();
+        let mut receiver = TaskContext::builder().name("Receiver").build();
+        let mut resolver = ChannelResolver;
+
+        let first = request(
+            TaskStep::Send("readings".into(), 1),
+            &mut sender,
+            &mut manager,
+        )?;
+        assert_eq!(
+            resolver.poll(&first, &sender, &mut manager),
+            Poll::Ready(RequestResult::Done)
+        );
+
+        // The channel only holds one value, so the second has to wait for room.
+        let second = request(
+            TaskStep::Send("readings".into(), 2),
+            &mut sender,
+            &mut manager,
+        )?;
+        assert_eq!(resolver.poll(&second, &sender, &mut manager), Poll::Pending);
+
+        let recv = TaskStep::Recv("readings".into(), Register::Int(0));
+        let recv = request(recv, &mut receiver, &mut manager)?;
+        waker::take();
+        assert_eq!(
+            resolver.poll(&recv, &receiver, &mut manager),
+            Poll::Ready(RequestResult::Value(1))
+        );
+        assert!(waker::take(), "Making room should wake the scheduler.");
+        assert_eq!(
+            resolver.poll(&second, &sender, &mut manager),
+            Poll::Ready(RequestResult::Done)
+        );
+        assert_eq!(
+            manager.channel("readings").map(|channel| channel.len()),
+            Some(1)
+        );
+        Ok(())
+    }
+
+    fn blocks_receiving_from_empty_channels() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        manager.create_channel("readings".into(), 4)?;
+        let mut sender = TaskContext::builder().name("Sender").build();
+        let mut receiver = TaskContext::builder().name("Receiver").build();
+        let mut resolver = ChannelResolver;
+
+        let recv = TaskStep::Recv("readings".into(), Register::Int(3));
+        let recv = request(recv, &mut receiver, &mut manager)?;
+        assert_eq!(resolver.poll(&recv, &receiver, &mut manager), Poll::Pending);
+
+        let send = request(
+            TaskStep::Send("readings".into(), 42),
+            &mut sender,
+            &mut manager,
+        )?;
+        assert_eq!(
+            resolver.poll(&send, &sender, &mut manager),
+            Poll::Ready(RequestResult::Done)
+        );
+
+        // The value is stored in the register chosen by the step.
+        let result = resolver.poll(&recv, &receiver, &mut manager);
+        assert_eq!(result, Poll::Ready(RequestResult::Value(42)));
+        if let Poll::Ready(result) = result {
+            receiver.write_back(result);
+        }
+        assert_eq!(receiver.registers().get(Register::Int(3))?, Value::Int(42));
+        assert_eq!(
+            manager.channel("readings").map(|channel| channel.len()),
+            Some(0)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+/// Describes the GPIO pins of a chip, and which of them are safe to use.
+///
+/// Reserved pins are wired to the SPI flash, or otherwise taken by the module,
+/// so using them crashes the chip and they are never handed out. Strapping pins
+/// are sampled at reset to choose how the chip boots, so they can be used once
+/// it is running, but driving them from outside can stop it from booting.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ChipProfile {
+    /// The name of the chip, as given to the `MCU` variable when building.
+    name: &'static str,
+    /// Every GPIO pin that the chip has.
+    pins: &'static [i32],
+    /// The pins that can only be read, as they have no output driver.
+    input_only: &'static [i32],
+    /// The pins sampled at reset to choose how the chip boots.
+    strapping: &'static [i32],
+    /// The pins that are wired to the SPI flash and can't be used.
+    reserved: &'static [i32],
+}
+
+impl ChipProfile {
+    /// The original ESP32, where GPIO 6 to 11 are the SPI flash and GPIO 34 to 39
+    /// have no output driver.
+    pub const ESP32: Self = Self {
+        name: "esp32",
+        pins: &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 21, 22, 23, 25,
+            26, 27, 32, 33, 34, 35, 36, 37, 38, 39,
+        ],
+        input_only: &[34, 35, 36, 37, 38, 39],
+        strapping: &[0, 2, 5, 12, 15],
+        reserved: &[6, 7, 8, 9, 10, 11],
+    };
+
+    /// The ESP32-S2, where GPIO 26 to 32 are the SPI flash and PSRAM.
+    pub const ESP32_S2: Self = Self {
+        name: "esp32s2",
+        pins: &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 26, 27,
+            28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46,
+        ],
+        input_only: &[46],
+        strapping: &[0, 45, 46],
+        reserved: &[26, 27, 28, 29, 30, 31, 32],
+    };
+
+    /// The ESP32-S3, where GPIO 26 to 32 are the SPI flash and PSRAM.
+    pub const ESP32_S3: Self = Self {
+        name: "esp32s3",
+        pins: &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 26, 27,
+            28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48,
+        ],
+        input_only: &[],
+        strapping: &[0, 3, 45, 46],
+        reserved: &[26, 27, 28, 29, 30, 31, 32],
+    };
+
+    /// The ESP32-C3, where GPIO 12 to 17 are the SPI flash.
+    pub const ESP32_C3: Self = Self {
+        name: "esp32c3",
+        pins: &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21,
+        ],
+        input_only: &[],
+        strapping: &[2, 8, 9],
+        reserved: &[12, 13, 14, 15, 16, 17],
+    };
+
+    /// The ESP32-C6, where GPIO 24 to 30 are the SPI flash.
+    pub const ESP32_C6: Self = Self {
+        name: "esp32c6",
+        pins: &[
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+            24, 25, 26, 27, 28, 29, 30,
+        ],
+        input_only: &[],
+        strapping: &[4, 5, 8, 9, 15],
+        reserved: &[24, 25, 26, 27, 28, 29, 30],
+    };
+
+    /// Every chip that there is a profile for.
+    pub const ALL: [Self; 5] = [
+        Self::ESP32,
+        Self::ESP32_S2,
+        Self::ESP32_S3,
+        Self::ESP32_C3,
+        Self::ESP32_C6,
+    ];
+
+    /// Find the profile of a chip by its name, such as `esp32c3`.
+    pub fn for_mcu(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|profile| profile.name == name)
+    }
+
+    /// The profile of the chip being built for, going by the `MCU` variable that
+    /// the build was given, or the original ESP32 if it wasn't given one.
+    pub fn current() -> Self {
+        // TODO: Warn when the chip is not one that there is a profile for.
+        option_env!("MCU")
+            .and_then(Self::for_mcu)
+            .unwrap_or(Self::ESP32)
+    }
+
+    /// The name of the chip.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Every GPIO pin that the chip has, including reserved pins.
+    pub fn pins(&self) -> &'static [i32] {
+        self.pins
+    }
+
+    /// The pins that can only be read, as they have no output driver.
+    pub fn input_only(&self) -> &'static [i32] {
+        self.input_only
+    }
+
+    /// The pins that can be handed out to tasks, which is every pin that isn't reserved.
+    pub fn usable_pins(&self) -> impl Iterator<Item = i32> + '_ {
+        self.pins
+            .iter()
+            .copied()
+            .filter(|pin| !self.is_reserved(*pin))
+    }
+
+    /// Whether the chip has the pin at all.
+    pub fn has_pin(&self, pin: i32) -> bool {
+        self.pins.contains(&pin)
+    }
+
+    /// Whether the pin can only be read.
+    pub fn is_input_only(&self, pin: i32) -> bool {
+        self.input_only.contains(&pin)
+    }
+
+    /// Whether the pin is sampled at reset to choose how the chip boots.
+    pub fn is_strapping(&self, pin: i32) -> bool {
+        self.strapping.contains(&pin)
+    }
+
+    /// Whether the pin is wired to the SPI flash, so it can't be used.
+    pub fn is_reserved(&self, pin: i32) -> bool {
+        self.reserved.contains(&pin)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::ChipProfile;
+    use crate::testing;
+
+    testing::tests!(leaves_out_flash_pins, knows_input_only_pins);
+
+    fn leaves_out_flash_pins() -> anyhow::Result<()> {
+        let usable: Vec<_> = ChipProfile::ESP32.usable_pins().collect();
+        assert!((6..=11).all(|pin| ChipProfile::ESP32.has_pin(pin) && !usable.contains(&pin)));
+        assert!(usable.contains(&5) && usable.contains(&12));
+        Ok(())
+    }
+
+    fn knows_input_only_pins() -> anyhow::Result<()> {
+        assert!(ChipProfile::ESP32.is_input_only(34));
+        assert!(!ChipProfile::ESP32.is_input_only(33));
+        assert!(!ChipProfile::ESP32_S3.is_input_only(34));
+        Ok(())
+    }
+}
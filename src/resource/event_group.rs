@@ -0,0 +1,55 @@
+/// Represents whether a task waiting on an event group needs all or any of its bits.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum WaitMode {
+    /// Every bit in the mask must be set.
+    All,
+    /// At least one bit in the mask must be set.
+    Any,
+}
+
+/// A group of event bits that can be set or cleared by any task or by host code.
+///
+/// This mirrors FreeRTOS event groups, allowing one task to wake any number of
+/// tasks that are waiting on the bits it sets.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct EventGroup {
+    /// The bits that are currently set.
+    bits: u32,
+}
+
+impl EventGroup {
+    /// Create a new event group with no bits set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the bits in the mask, returning the bits that are now set.
+    pub fn set(&mut self, mask: u32) -> u32 {
+        self.bits |= mask;
+        self.bits
+    }
+
+    /// Clear the bits in the mask, returning the bits that are now set.
+    pub fn clear(&mut self, mask: u32) -> u32 {
+        self.bits &= !mask;
+        self.bits
+    }
+
+    /// The bits that are currently set.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Check whether the bits in the mask satisfy the given wait mode.
+    pub fn satisfies(&self, mask: u32, mode: WaitMode) -> bool {
+        match mode {
+            WaitMode::All => self.bits & mask == mask,
+            WaitMode::Any => self.bits & mask != 0,
+        }
+    }
+}
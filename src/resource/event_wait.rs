@@ -0,0 +1,152 @@
+use std::{collections::HashMap, task::Poll};
+
+use super::{Request, RequestResolver, RequestResult, ResourceManager};
+use crate::task::TaskContext;
+
+/// Resolves requests that wait for bits to be set in an event group.
+///
+/// As with FreeRTOS, bits that are cleared on exit are only cleared once every
+/// waiting task has been checked, so a single set can wake all of them.
+#[derive(Debug, Default, Clone)]
+pub struct EventResolver {
+    /// The bits to clear from each event group once every waiting task has been checked.
+    pending_clears: HashMap<String, u32>,
+}
+
+impl RequestResolver for EventResolver {
+    fn poll(
+        &mut self,
+        request: &Request,
+        _context: &TaskContext,
+        manager: &mut ResourceManager,
+    ) -> Poll<RequestResult> {
+        let Request::WaitBits(name, mask, mode, clear_on_exit) = request else {
+            return Poll::Pending;
+        };
+
+        match manager.event_group(name) {
+            Some(group) if group.satisfies(*mask, *mode) => {
+                if *clear_on_exit {
+                    *self.pending_clears.entry(name.clone()).or_default() |= *mask;
+                }
+                Poll::Ready(RequestResult::Bits(group.bits()))
+            }
+            _ => Poll::Pending,
+        }
+    }
+
+    fn finish(&mut self, manager: &mut ResourceManager) {
+        for (name, mask) in self.pending_clears.drain() {
+            if let Some(group) = manager.event_group_mut(&name) {
+                group.clear(mask);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::task::Poll;
+
+    use anyhow::Context;
+
+    use super::EventResolver;
+    use crate::{
+        resource::{Request, RequestResolver, RequestResult, ResourceManager, WaitMode},
+        task::{TaskContext, TaskStep},
+        testing,
+    };
+
+    testing::tests!(
+        waits_for_all_bits,
+        waits_for_any_bit,
+        wakes_every_waiter_before_clearing
+    );
+
+    /// Run a step that waits on the event group `events`, returning the request
+    /// that it blocks on.
+    fn wait(
+        manager: &mut ResourceManager,
+        mask: u32,
+        mode: WaitMode,
+        clear_on_exit: bool,
+    ) -> anyhow::Result<(TaskContext, Request)> {
+        let mut context = TaskContext::builder().name("Waiter").build();
+        let request = TaskStep::WaitBits("events".into(), mask, mode, clear_on_exit)
+            .execute(&mut context, manager)?
+            .context("Waiting on bits should block the task!")?;
+        Ok((context, request))
+    }
+
+    fn bits(manager: &ResourceManager) -> Option<u32> {
+        manager.event_group("events").map(|group| group.bits())
+    }
+
+    fn waits_for_all_bits() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        manager.create_event_group("events".into())?;
+        let (context, request) = wait(&mut manager, 0b011, WaitMode::All, true)?;
+        let mut resolver = EventResolver::default();
+
+        manager.set_bits("events", 0b101)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Pending
+        );
+
+        manager.set_bits("events", 0b010)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Ready(RequestResult::Bits(0b111))
+        );
+
+        // Only the bits that were waited on are cleared, once polling is done.
+        assert_eq!(bits(&manager), Some(0b111));
+        resolver.finish(&mut manager);
+        assert_eq!(bits(&manager), Some(0b100));
+        Ok(())
+    }
+
+    fn waits_for_any_bit() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        manager.create_event_group("events".into())?;
+        let (context, request) = wait(&mut manager, 0b110, WaitMode::Any, false)?;
+        let mut resolver = EventResolver::default();
+
+        manager.set_bits("events", 0b001)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Pending
+        );
+
+        manager.set_bits("events", 0b100)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Ready(RequestResult::Bits(0b101))
+        );
+        resolver.finish(&mut manager);
+        assert_eq!(bits(&manager), Some(0b101));
+        Ok(())
+    }
+
+    fn wakes_every_waiter_before_clearing() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        manager.create_event_group("events".into())?;
+        let (first, first_request) = wait(&mut manager, 0b1, WaitMode::All, true)?;
+        let (second, second_request) = wait(&mut manager, 0b1, WaitMode::Any, true)?;
+        let mut resolver = EventResolver::default();
+
+        manager.set_bits("events", 0b1)?;
+        assert_eq!(
+            resolver.poll(&first_request, &first, &mut manager),
+            Poll::Ready(RequestResult::Bits(0b1))
+        );
+        assert_eq!(
+            resolver.poll(&second_request, &second, &mut manager),
+            Poll::Ready(RequestResult::Bits(0b1))
+        );
+        resolver.finish(&mut manager);
+        assert_eq!(bits(&manager), Some(0));
+        Ok(())
+    }
+}
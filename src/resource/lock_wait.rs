@@ -0,0 +1,121 @@
+use std::task::Poll;
+
+use super::{Request, RequestResolver, RequestResult, ResourceManager};
+use crate::task::TaskContext;
+
+/// Resolves requests that acquire a permit from a semaphore or mutex.
+///
+/// The scheduler polls blocked tasks in priority order, so when a permit is
+/// released it goes to the highest priority task waiting for it. With deadlock
+/// avoidance turned on, a permit is only handed out if the resulting state is
+/// safe, and the task stays blocked otherwise.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LockResolver;
+
+impl RequestResolver for LockResolver {
+    fn poll(
+        &mut self,
+        request: &Request,
+        context: &TaskContext,
+        manager: &mut ResourceManager,
+    ) -> Poll<RequestResult> {
+        match request {
+            Request::Acquire(name)
+                if (!manager.avoidance() || manager.can_grant(name, *context.id()))
+                    && manager
+                        .semaphore_mut(name)
+                        .is_some_and(|semaphore| semaphore.try_acquire(*context.id())) =>
+            {
+                Poll::Ready(RequestResult::Done)
+            }
+            _ => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::BTreeMap, task::Poll};
+
+    use anyhow::Context;
+
+    use super::LockResolver;
+    use crate::{
+        resource::{RequestResolver, RequestResult, ResourceManager, Semaphore},
+        task::{TaskContext, TaskStep},
+        testing,
+    };
+
+    testing::tests!(refuses_unsafe_acquire);
+
+    /// Set up the state of figure 6.8 of Stallings, with a semaphore for each
+    /// class of resource and a task for each process.
+    fn figure_6_8(manager: &mut ResourceManager) -> anyhow::Result<Vec<TaskContext>> {
+        let names = ["r1", "r2", "r3"];
+        for (name, permits) in names.iter().zip([9, 3, 6]) {
+            manager.create_semaphore(name.to_string(), Semaphore::new(permits))?;
+        }
+
+        let claims = [[3, 2, 2], [6, 1, 3], [3, 1, 4], [4, 2, 2]];
+        let allocations = [[1, 0, 0], [5, 1, 1], [2, 1, 1], [0, 0, 2]];
+        let mut tasks = vec![];
+        for (process, (claim, allocation)) in claims.iter().zip(allocations).enumerate() {
+            let context = TaskContext::builder()
+                .name(format!("P{}", process + 1))
+                .max_claims(BTreeMap::from_iter(
+                    names.iter().map(|name| name.to_string()).zip(*claim),
+                ))
+                .build();
+            manager.claim(&context)?;
+
+            for (name, held) in names.iter().zip(allocation) {
+                for _ in 0..held {
+                    let semaphore = manager.semaphore_mut(name).context("Missing semaphore!")?;
+                    semaphore.try_acquire(*context.id());
+                }
+            }
+            tasks.push(context);
+        }
+        Ok(tasks)
+    }
+
+    fn refuses_unsafe_acquire() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        let mut tasks = figure_6_8(&mut manager)?;
+        manager.set_avoidance(true);
+        assert!(manager.is_safe());
+
+        // P1 taking a unit of R1 would leave no task able to finish, but P2 taking
+        // it could still finish straight away.
+        assert!(!manager.can_grant("r1", *tasks[0].id()));
+        assert!(manager.can_grant("r1", *tasks[1].id()));
+
+        let step = TaskStep::Acquire("r1".into());
+        let mut resolver = LockResolver;
+        let request = step
+            .execute(&mut tasks[0], &mut manager)?
+            .context("Acquiring should block the task!")?;
+        assert_eq!(
+            resolver.poll(&request, &tasks[0], &mut manager),
+            Poll::Pending
+        );
+        let held = |manager: &ResourceManager, context: &TaskContext| {
+            manager.semaphore("r1").map_or(0, |semaphore| {
+                let holders = semaphore.holders().iter();
+                holders.filter(|holder| *holder == context.id()).count()
+            })
+        };
+        assert_eq!(held(&manager, &tasks[0]), 1);
+
+        let request = step
+            .execute(&mut tasks[1], &mut manager)?
+            .context("Acquiring should block the task!")?;
+        assert_eq!(
+            resolver.poll(&request, &tasks[1], &mut manager),
+            Poll::Ready(RequestResult::Done)
+        );
+        assert_eq!(held(&manager, &tasks[1]), 6);
+        assert!(manager.is_safe());
+        Ok(())
+    }
+}
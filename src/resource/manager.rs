@@ -1,13 +1,22 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
 use anyhow::Context;
 use esp_idf_hal::{
-    gpio::AnyIOPin,
+    gpio::{AnyIOPin, AnyInputPin, Level},
     peripheral::{Peripheral, PeripheralRef},
     prelude::Peripherals,
+    sys::{esp, gpio_intr_enable},
 };
 
-use crate::task::TaskContext;
+use crate::{scheduler::waker, task::TaskContext};
 
-use super::TaskResource;
+use super::{
+    Access, BankerState, CachedPin, Channel, ChipProfile, EventGroup, GpioPin, Owner,
+    OwnershipTable, PinMode, PinWatch, ResourceRef, Semaphore, TaskResource,
+};
 
 /// An asset manager but for IO resources.
 ///
@@ -15,43 +24,119 @@ use super::TaskResource;
 /// any IO device or resource. It is unsafe to access a resource using a way
 /// other than the 'ResourceManager', and it will lead to undefined behaviour.
 pub struct ResourceManager<'a> {
-    /// A list of peripherals or pins that it will manage.
-    peripherals: Peripherals,
-    /// A ready to access list of references to GPIO pins.
-    pins: Vec<PeripheralRef<'a, AnyIOPin>>,
+    /// The chip being run on, which decides which pins there are.
+    profile: ChipProfile,
+    /// Every usable GPIO pin by its number, each with the driver it was last configured with.
+    pins: BTreeMap<i32, CachedPin<'a>>,
+    /// The resources currently handed out, along with the task holding each of them.
+    held: HashMap<TaskResource, uuid::Uuid>,
+    /// The pins that are being watched for changes through GPIO interrupts.
+    watches: HashMap<i32, Arc<PinWatch>>,
+    /// The channels that tasks can use to communicate, by name.
+    channels: HashMap<String, Channel>,
+    /// The semaphores and mutexes that tasks can use to guard shared hardware, by name.
+    semaphores: HashMap<String, Semaphore>,
+    /// The event groups that tasks can use to wake each other, by name.
+    event_groups: HashMap<String, EventGroup>,
+    /// Which tasks own which resources.
+    ownership: OwnershipTable,
+    /// The most permits of each semaphore that each task may hold at once, by task id.
+    claims: HashMap<uuid::Uuid, BTreeMap<String, usize>>,
+    /// Whether permits are only handed out when doing so leaves the system in a
+    /// safe state, as in the Banker's algorithm.
+    avoidance: bool,
     // TODO: Add support for resources other than pins.
-    // TODO: Add support for keeping track of which task has what resource.
 }
 
 impl<'a> ResourceManager<'a> {
-    /// Create a new resource manager.
+    /// Create a new resource manager for the chip being built for.
     ///
     /// This will setup all the references it needs to all the peripheral devices that
     /// it manages. Additional pins can also be accessed from it.
     pub fn new() -> anyhow::Result<Self> {
-        let mut peripherals =
-            Peripherals::take().context("Peripherals have already been taken.")?;
+        let peripherals = Peripherals::take().context("Peripherals have already been taken.")?;
+        Ok(Self::from_peripherals(peripherals))
+    }
+
+    /// Create a new resource manager for a test, which doesn't take the peripherals
+    /// so that every test can have its own.
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        // Safety: Tests are run one at a time, so only one manager ever uses the pins.
+        Self::from_peripherals(unsafe { Peripherals::new() })
+    }
+
+    /// Create a new resource manager that hands out every pin of the chip being
+    /// built for that isn't reserved.
+    ///
+    /// The peripherals are taken so that nothing else can reach the pins.
+    fn from_peripherals(peripherals: Peripherals) -> Self {
+        let profile = ChipProfile::current();
+        let mut pins: BTreeMap<_, _> = Self::io_pins(peripherals)
+            .into_iter()
+            .map(|(number, pin)| (number, CachedPin::new(number, pin)))
+            .collect();
 
-        // TODO: Consider creating a more generic version of this.
-        // Safety: This operation is safe as only this struct will ever have access
-        // to these resources at all times.
-        let pins = util::pref!(pins: 0..21);
+        // Safety: The peripherals were handed over, so only this struct will ever
+        // have access to their pins. The profile is the one of the chip being
+        // built for, so every input-only pin it lists exists.
+        for &number in profile.input_only() {
+            let pin = unsafe { AnyInputPin::new(number) }.into_ref();
+            pins.insert(number, CachedPin::input_only(number, pin));
+        }
 
-        Ok(Self { peripherals, pins })
+        Self {
+            profile,
+            pins,
+            held: HashMap::new(),
+            watches: HashMap::new(),
+            channels: HashMap::new(),
+            semaphores: HashMap::new(),
+            event_groups: HashMap::new(),
+            ownership: OwnershipTable::new(),
+            claims: HashMap::new(),
+            avoidance: false,
+        }
     }
 
-    // TODO: Modify this function to return a ResourceRef instead.
-    // A ResourceRef is similar to a PeripheralRef however is generic over all
-    // resources, and provides a simpler API.
+    /// Take every pin of the chip being built for that can both read and drive
+    /// and isn't reserved, which leaves out the pins of its `ChipProfile` that
+    /// are input-only.
+    ///
+    /// Each pin is taken by its field on the peripherals, so a pin that the chip
+    /// doesn't have, or that is input-only, fails to compile.
+    fn io_pins(peripherals: Peripherals) -> BTreeMap<i32, PeripheralRef<'a, AnyIOPin>> {
+        // Safety: The peripherals are consumed, so these are the only references
+        // to their pins that will ever be made.
+        #[cfg(esp32s2)]
+        return util::pref!(peripherals.pins, 0..=21, 33..=45);
+        #[cfg(esp32s3)]
+        return util::pref!(peripherals.pins, 0..=21, 33..=48);
+        #[cfg(esp32c3)]
+        return util::pref!(peripherals.pins, 0..=21 except 12..=17);
+        #[cfg(esp32c6)]
+        return util::pref!(peripherals.pins, 0..=23);
+        // The original ESP32 is also what the host builds for, as in `ChipProfile::current`.
+        #[cfg(not(any(esp32s2, esp32s3, esp32c3, esp32c6)))]
+        return util::pref!(peripherals.pins, 0..=33 except 6..=11, 20, 24, 28..=31);
+    }
 
     /// This method safely checks whether a task has declared a pin before giving
     /// access to it. This prevents two tasks from using the same pin at the same
     /// time which may lead to undefined behaviour.
+    ///
+    /// The pin must also have been declared in a mode that allows the access, so
+    /// a task can't write to a pin that it declared as an input. The pin is held
+    /// by the task until the returned handle is dropped.
+    ///
+    /// The pin is set up as the task configured it, and is driven to its initial
+    /// level the first time it is acquired by a task that may write to it.
     pub fn acquire(
         &mut self,
         resource: TaskResource,
-        context: &mut TaskContext,
-    ) -> anyhow::Result<&mut PeripheralRef<'a, AnyIOPin>> {
+        access: Access,
+        context: &TaskContext,
+    ) -> anyhow::Result<ResourceRef<'_, 'a, GpioPin>> {
         match resource {
             TaskResource::Pin(number) => {
                 context
@@ -60,10 +145,433 @@ impl<'a> ResourceManager<'a> {
                     .then_some(())
                     .context("Cannot acquire pin that hasn't been declared!")?;
 
-                self.pins
-                    .get_mut(number as usize)
-                    .context(format!("Pin {number} not found!"))
+                let mode = context.pin_mode(number);
+                mode.allows(access).then_some(()).context(format!(
+                    "Cannot {access} pin {number} as it was declared as {mode}!"
+                ))?;
+
+                // Handles borrow the manager, so a pin can only still be held if its
+                // handle was leaked rather than dropped.
+                (!self.held.contains_key(&resource))
+                    .then_some(())
+                    .context(format!("Pin {number} is still held by another handle!"))?;
+
+                let pin = self
+                    .pins
+                    .get_mut(&number)
+                    .context(format!("Pin {number} not found!"))?;
+
+                // The configuration is applied before the handle is handed out, so
+                // the step using the pin already sees it set up.
+                pin.set_config(context.pin_config(number))?;
+                if mode.allows(Access::Write) {
+                    pin.drive_initial_level()?;
+                }
+
+                Ok(ResourceRef::new(
+                    resource,
+                    *context.id(),
+                    mode,
+                    pin,
+                    &mut self.held,
+                ))
+            }
+        }
+    }
+
+    /// Start watching a pin for changes in its level using a GPIO interrupt.
+    ///
+    /// The task must have declared the pin, just as with `acquire`. A pin is only
+    /// subscribed to once, so watching it again returns the existing watch.
+    pub fn watch(
+        &mut self,
+        resource: TaskResource,
+        context: &TaskContext,
+    ) -> anyhow::Result<Arc<PinWatch>> {
+        match resource {
+            TaskResource::Pin(number) => {
+                if let Some(watch) = self.watched(number) {
+                    self.acquire(resource, Access::Read, context)?;
+                    return Ok(watch);
+                }
+
+                let mut pin = self.acquire(resource, Access::Read, context)?;
+                let watch = Arc::new(PinWatch::new(pin.read()?));
+                pin.watch(watch.clone())?;
+                drop(pin);
+
+                self.watches.insert(number, watch.clone());
+                Ok(watch)
             }
         }
     }
+
+    /// Claim every resource declared by a task, failing if another task owns any
+    /// of them in a way that conflicts.
+    ///
+    /// The maximum claims of the task are recorded too, which must be for
+    /// semaphores that exist and must not be for more permits than they have.
+    pub fn claim(&mut self, context: &TaskContext) -> anyhow::Result<()> {
+        for (name, claim) in context.max_claims() {
+            let semaphore = self.semaphores.get(name).context(format!(
+                "Task '{}' claims semaphore {name}, which has not been registered!",
+                context.name()
+            ))?;
+            (*claim <= semaphore.permits())
+                .then_some(())
+                .context(format!(
+                    "Task '{}' claims {claim} permits of {name}, but it only has {}!",
+                    context.name(),
+                    semaphore.permits()
+                ))?;
+        }
+
+        self.ownership.claim(context)?;
+        if !context.max_claims().is_empty() {
+            self.claims
+                .insert(*context.id(), context.max_claims().clone());
+        }
+        Ok(())
+    }
+
+    /// Release every resource owned by the task with the given id, so that other
+    /// tasks can claim them.
+    ///
+    /// Pins that no task owns any more forget the level they were driven to, so
+    /// the next task to own one drives it to its own initial level.
+    pub fn disown(&mut self, id: uuid::Uuid) {
+        for resource in self.ownership.release_all(id) {
+            let TaskResource::Pin(number) = resource;
+            if let Some(pin) = self.pins.get_mut(&number) {
+                pin.forget_level();
+            }
+        }
+        self.claims.remove(&id);
+    }
+
+    /// The tasks that currently own a resource.
+    pub fn owners(&self, resource: TaskResource) -> &[Owner] {
+        self.ownership.owners(resource)
+    }
+
+    /// The chip being run on.
+    pub fn profile(&self) -> &ChipProfile {
+        &self.profile
+    }
+
+    /// Whether the given pin number is one of the pins that this manager hands out.
+    pub fn is_valid_pin(&self, number: i32) -> bool {
+        self.pins.contains_key(&number)
+    }
+
+    /// The mode that the driver of a pin is currently configured in.
+    pub fn pin_mode(&self, number: i32) -> Option<PinMode> {
+        self.pins.get(&number).map(CachedPin::mode)
+    }
+
+    /// How many times the driver of a pin has been reconfigured.
+    pub fn reconfigurations(&self, number: i32) -> Option<usize> {
+        self.pins.get(&number).map(CachedPin::reconfigurations)
+    }
+
+    /// Get the watch on a pin, if it is being watched.
+    pub fn watched(&self, number: i32) -> Option<Arc<PinWatch>> {
+        self.watches.get(&number).cloned()
+    }
+
+    /// Enable the interrupt on a watched pin again after it has fired.
+    pub fn rearm(&mut self, number: i32) -> anyhow::Result<()> {
+        self.watches
+            .contains_key(&number)
+            .then_some(())
+            .context(format!("Pin {number} is not being watched!"))?;
+
+        // Safety: The pin is owned by this manager and already has an interrupt
+        // handler subscribed to it.
+        esp!(unsafe { gpio_intr_enable(number) })?;
+        Ok(())
+    }
+
+    /// Start watching a pin as if it were at the given level, without configuring
+    /// it or subscribing to its interrupt.
+    ///
+    /// The pin then only ever changes through `simulate_pin_change`, which allows
+    /// tasks that wait on pins to be tested without real hardware. A pin that is
+    /// already watched keeps its watch.
+    pub fn simulate_watch(&mut self, number: i32, level: Level) -> anyhow::Result<Arc<PinWatch>> {
+        self.is_valid_pin(number)
+            .then_some(())
+            .context(format!("Pin {number} is not one of the pins of the chip!"))?;
+
+        Ok(self
+            .watches
+            .entry(number)
+            .or_insert_with(|| Arc::new(PinWatch::new(level)))
+            .clone())
+    }
+
+    /// Simulate a pin changing to the given level, as if its interrupt fired.
+    ///
+    /// A pin that isn't watched yet starts being watched at the other level
+    /// through `simulate_watch`, so the change always counts as an edge.
+    pub fn simulate_pin_change(&mut self, number: i32, level: Level) -> anyhow::Result<()> {
+        let other = if level == Level::High {
+            Level::Low
+        } else {
+            Level::High
+        };
+        self.simulate_watch(number, other)?.record(level);
+        waker::wake();
+        Ok(())
+    }
+
+    /// Register a new channel with the given name that can hold up to `capacity` values.
+    pub fn create_channel(&mut self, name: String, capacity: usize) -> anyhow::Result<()> {
+        (capacity > 0)
+            .then_some(())
+            .context("A channel must be able to hold at least one value!")?;
+        (!self.channels.contains_key(&name))
+            .then_some(())
+            .context(format!("Channel {name} has already been registered!"))?;
+
+        self.channels.insert(name, Channel::new(capacity));
+        Ok(())
+    }
+
+    /// Get the channel with the given name, if it has been registered.
+    pub fn channel(&self, name: &str) -> Option<&Channel> {
+        self.channels.get(name)
+    }
+
+    /// Get mutable access to the channel with the given name, if it has been registered.
+    pub fn channel_mut(&mut self, name: &str) -> Option<&mut Channel> {
+        self.channels.get_mut(name)
+    }
+
+    /// Register a new semaphore or mutex with the given name.
+    pub fn create_semaphore(&mut self, name: String, semaphore: Semaphore) -> anyhow::Result<()> {
+        (!self.semaphores.contains_key(&name))
+            .then_some(())
+            .context(format!("Semaphore {name} has already been registered!"))?;
+
+        self.semaphores.insert(name, semaphore);
+        Ok(())
+    }
+
+    /// Get the semaphore or mutex with the given name, if it has been registered.
+    pub fn semaphore(&self, name: &str) -> Option<&Semaphore> {
+        self.semaphores.get(name)
+    }
+
+    /// Get mutable access to the semaphore or mutex with the given name, if it has been registered.
+    pub fn semaphore_mut(&mut self, name: &str) -> Option<&mut Semaphore> {
+        self.semaphores.get_mut(name)
+    }
+
+    /// Give back a permit that the task holds on the named semaphore or mutex.
+    pub fn release(&mut self, name: &str, context: &TaskContext) -> anyhow::Result<()> {
+        self.semaphores
+            .get_mut(name)
+            .context(format!("Semaphore {name} has not been registered!"))?
+            .release(*context.id())
+            .then_some(())
+            .context(format!(
+                "Cannot release {name} as it is not held by this task!"
+            ))?;
+
+        waker::wake();
+        Ok(())
+    }
+
+    /// Turn on or off deadlock avoidance, where a permit is only handed out if every
+    /// task could still be given the rest of its maximum claim in some order.
+    pub fn set_avoidance(&mut self, avoidance: bool) {
+        self.avoidance = avoidance;
+    }
+
+    /// Whether deadlock avoidance is turned on.
+    pub fn avoidance(&self) -> bool {
+        self.avoidance
+    }
+
+    /// Check that taking one more permit of the named semaphore stays within the
+    /// maximum claim of the task, which is only needed with deadlock avoidance.
+    pub fn check_claim(&self, name: &str, context: &TaskContext) -> anyhow::Result<()> {
+        if !self.avoidance {
+            return Ok(());
+        }
+
+        let claim = context.max_claims().get(name).copied().unwrap_or_default();
+        let held = self.held(name, *context.id());
+        (held < claim).then_some(()).context(format!(
+            "Cannot acquire {name} as the task already holds {held} of its maximum claim of {claim}!"
+        ))
+    }
+
+    /// The state of every semaphore and every task holding or claiming one, for
+    /// the Banker's algorithm.
+    ///
+    /// Resource classes are the semaphores, sorted by name, and processes are the
+    /// tasks, sorted by id. Tasks that hold permits without claiming them are
+    /// treated as claiming just what they hold.
+    pub fn banker_state(&self) -> BankerState {
+        let (_, _, state) = self.banker();
+        state
+    }
+
+    /// Whether every task could still be given the rest of its maximum claim.
+    pub fn is_safe(&self) -> bool {
+        self.banker_state().is_safe()
+    }
+
+    /// Whether giving the task with the given id a permit of the named semaphore
+    /// leaves the system in a safe state.
+    pub fn can_grant(&self, name: &str, id: uuid::Uuid) -> bool {
+        let (processes, classes, state) = self.banker();
+        let (Some(process), Some(class)) = (
+            processes.iter().position(|process| *process == id),
+            classes.iter().position(|class| *class == name),
+        ) else {
+            return false;
+        };
+
+        let mut request = vec![0; classes.len()];
+        request[class] = 1;
+        state.can_grant(process, &request)
+    }
+
+    /// The number of permits of the named semaphore held by the task with the given id.
+    fn held(&self, name: &str, id: uuid::Uuid) -> usize {
+        self.semaphores.get(name).map_or(0, |semaphore| {
+            semaphore
+                .holders()
+                .iter()
+                .filter(|holder| **holder == id)
+                .count()
+        })
+    }
+
+    /// Build the state for the Banker's algorithm, along with the task id of each
+    /// process and the semaphore name of each class of resource.
+    fn banker(&self) -> (Vec<uuid::Uuid>, Vec<&str>, BankerState) {
+        let mut classes: Vec<&str> = self.semaphores.keys().map(String::as_str).collect();
+        classes.sort();
+
+        let mut processes: Vec<uuid::Uuid> = self
+            .claims
+            .keys()
+            .chain(self.semaphores.values().flat_map(Semaphore::holders))
+            .copied()
+            .collect();
+        processes.sort();
+        processes.dedup();
+
+        let resources = classes
+            .iter()
+            .map(|class| self.semaphores[*class].permits())
+            .collect();
+        let allocation: Vec<Vec<usize>> = processes
+            .iter()
+            .map(|id| classes.iter().map(|class| self.held(class, *id)).collect())
+            .collect();
+        let claim = processes
+            .iter()
+            .zip(&allocation)
+            .map(|(id, held)| {
+                let claims = self.claims.get(id);
+                classes
+                    .iter()
+                    .zip(held)
+                    .map(|(class, held)| {
+                        let claim = claims.and_then(|claims| claims.get(*class));
+                        claim.copied().unwrap_or_default().max(*held)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Permits are never held beyond what exists, and claims are checked against
+        // what exists when they are made, so the state is always valid.
+        let state = BankerState::new(resources, claim, allocation)
+            .expect("The semaphores and claims always make a valid state.");
+        (processes, classes, state)
+    }
+
+    /// Give back every permit held by the task with the given id.
+    ///
+    /// Returns the names of the semaphores and mutexes that permits were released from.
+    pub fn release_all(&mut self, holder: uuid::Uuid) -> Vec<String> {
+        let released: Vec<String> = self
+            .semaphores
+            .iter_mut()
+            .filter_map(|(name, semaphore)| {
+                (semaphore.release_all(holder) > 0).then(|| name.clone())
+            })
+            .collect();
+
+        if !released.is_empty() {
+            waker::wake();
+        }
+        released
+    }
+
+    /// Register a new event group with the given name and no bits set.
+    pub fn create_event_group(&mut self, name: String) -> anyhow::Result<()> {
+        (!self.event_groups.contains_key(&name))
+            .then_some(())
+            .context(format!("Event group {name} has already been registered!"))?;
+
+        self.event_groups.insert(name, EventGroup::new());
+        Ok(())
+    }
+
+    /// Get the event group with the given name, if it has been registered.
+    pub fn event_group(&self, name: &str) -> Option<&EventGroup> {
+        self.event_groups.get(name)
+    }
+
+    /// Get mutable access to the event group with the given name, if it has been registered.
+    pub fn event_group_mut(&mut self, name: &str) -> Option<&mut EventGroup> {
+        self.event_groups.get_mut(name)
+    }
+
+    /// Set the bits in the mask on the named event group, returning the bits now set.
+    pub fn set_bits(&mut self, name: &str, mask: u32) -> anyhow::Result<u32> {
+        let bits = self
+            .event_groups
+            .get_mut(name)
+            .context(format!("Event group {name} has not been registered!"))?
+            .set(mask);
+
+        waker::wake();
+        Ok(bits)
+    }
+
+    /// Clear the bits in the mask on the named event group, returning the bits still set.
+    pub fn clear_bits(&mut self, name: &str, mask: u32) -> anyhow::Result<u32> {
+        Ok(self
+            .event_groups
+            .get_mut(name)
+            .context(format!("Event group {name} has not been registered!"))?
+            .clear(mask))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::ResourceManager;
+    use crate::testing;
+
+    testing::tests!(takes_every_usable_pin);
+
+    fn takes_every_usable_pin() -> anyhow::Result<()> {
+        let manager = ResourceManager::for_tests();
+        let profile = manager.profile();
+        let pins: Vec<_> = manager.pins.keys().copied().collect();
+        assert_eq!(pins, profile.usable_pins().collect::<Vec<_>>());
+        for (number, pin) in &manager.pins {
+            assert_eq!(pin.is_input_only(), profile.is_input_only(*number));
+        }
+        Ok(())
+    }
 }
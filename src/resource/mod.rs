@@ -1,7 +1,55 @@
+mod access;
+mod banker;
+mod channel;
+mod channel_wait;
+mod chip;
+mod event_group;
+mod event_wait;
+mod lock_wait;
 mod manager;
+mod ownership;
+mod pin_config;
+mod pin_driver;
+mod pin_wait;
 mod request;
+mod resolver;
 mod resource;
+mod resource_ref;
+mod semaphore;
+mod timer;
+mod watch;
 
+pub use access::{Access, AccessMode};
+pub use banker::BankerState;
+pub use channel::Channel;
+pub use channel_wait::ChannelResolver;
+pub use chip::ChipProfile;
+pub use event_group::{EventGroup, WaitMode};
+pub use event_wait::EventResolver;
+pub use lock_wait::LockResolver;
 pub use manager::ResourceManager;
-pub use request::Request;
+pub use ownership::{Owner, OwnershipTable};
+pub use pin_config::{DriveCapability, PinConfig, PullMode};
+pub use pin_driver::{CachedPin, PinMode};
+pub use pin_wait::PinWaitResolver;
+pub use request::{Request, RequestKind};
+pub use resolver::{RequestResolver, RequestResult};
 pub use resource::TaskResource;
+pub use resource_ref::{GpioPin, ResourceRef};
+pub use semaphore::Semaphore;
+pub use timer::TimerResolver;
+pub use watch::{Edge, PinWatch};
+
+/// The tests of every module in here.
+#[cfg(test)]
+pub const TESTS: &[&[crate::testing::Test]] = &[
+    banker::tests::TESTS,
+    channel_wait::tests::TESTS,
+    chip::tests::TESTS,
+    event_wait::tests::TESTS,
+    lock_wait::tests::TESTS,
+    manager::tests::TESTS,
+    ownership::tests::TESTS,
+    pin_wait::tests::TESTS,
+    semaphore::tests::TESTS,
+];
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+use getset::Getters;
+
+use super::{AccessMode, PinConfig, TaskResource};
+use crate::task::TaskContext;
+
+/// Represents a task that owns a resource.
+#[derive(Debug, PartialEq, Eq, Clone, Getters)]
+pub struct Owner {
+    /// The unique id of the task.
+    #[getset(get = "pub")]
+    id: uuid::Uuid,
+    /// The friendly display name of the task.
+    #[getset(get = "pub")]
+    name: String,
+    /// How the task holds the resource.
+    #[getset(get = "pub")]
+    mode: AccessMode,
+    /// How the task has the resource set up.
+    #[getset(get = "pub")]
+    config: PinConfig,
+}
+
+/// Keeps track of which tasks own which resources.
+///
+/// Tasks claim every resource they declare when they are scheduled, and keep
+/// them until they exit, so two tasks can never both be driving the same pin
+/// unless they both agreed to share it. Any number of tasks may hold a pin that
+/// they all only read. Tasks holding the same pin must also agree on how it is
+/// configured.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct OwnershipTable {
+    owners: HashMap<TaskResource, Vec<Owner>>,
+}
+
+impl OwnershipTable {
+    /// Create a new table where nothing is owned.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim every resource that a task declares.
+    ///
+    /// Either every resource is claimed, or none of them are and the error lists
+    /// every conflict with the tasks that already own them.
+    pub fn claim(&mut self, context: &TaskContext) -> anyhow::Result<()> {
+        let claims: Vec<_> = context
+            .pins_used()
+            .iter()
+            .map(|pin| {
+                let resource = TaskResource::Pin(*pin);
+                (resource, context.pin_mode(*pin), context.pin_config(*pin))
+            })
+            .collect();
+
+        let conflicts: Vec<String> = claims
+            .iter()
+            .flat_map(|(resource, mode, config)| {
+                self.owners(*resource)
+                    .iter()
+                    .filter_map(move |owner| match owner.mode {
+                        AccessMode::Output => Some(format!(
+                            "{resource} is already owned by task '{}'.",
+                            owner.name
+                        )),
+                        _ if !owner.mode.compatible(*mode) => Some(format!(
+                            "{resource} is held as {} by task '{}', but is declared as {mode}.",
+                            owner.mode, owner.name
+                        )),
+                        _ if owner.config != *config => Some(format!(
+                            "{resource} is configured as {} by task '{}', but as {config} here.",
+                            owner.config, owner.name
+                        )),
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        if !conflicts.is_empty() {
+            bail!(
+                "Task '{}' declares resources owned by other tasks:\n  - {}",
+                context.name(),
+                conflicts.join("\n  - ")
+            );
+        }
+
+        for (resource, mode, config) in claims {
+            self.owners.entry(resource).or_default().push(Owner {
+                id: *context.id(),
+                name: context.name().clone(),
+                mode,
+                config,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Release every resource owned by the task with the given id.
+    ///
+    /// Returns the resources that were owned by the task and by no other task.
+    pub fn release_all(&mut self, id: uuid::Uuid) -> Vec<TaskResource> {
+        let mut freed = vec![];
+        self.owners.retain(|resource, owners| {
+            let before = owners.len();
+            owners.retain(|owner| owner.id != id);
+            if owners.is_empty() && before > 0 {
+                freed.push(*resource);
+            }
+            !owners.is_empty()
+        });
+        freed
+    }
+
+    /// The tasks that currently own a resource.
+    pub fn owners(&self, resource: TaskResource) -> &[Owner] {
+        self.owners.get(&resource).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::BTreeMap;
+
+    use super::OwnershipTable;
+    use crate::{
+        resource::{AccessMode, TaskResource},
+        task::TaskContext,
+        testing,
+    };
+
+    testing::tests!(release_frees_unshared_pins);
+
+    fn release_frees_unshared_pins() -> anyhow::Result<()> {
+        let first = TaskContext::builder()
+            .name("First")
+            .pins_used(vec![2, 4])
+            .pin_modes(BTreeMap::from([(4, AccessMode::Shared)]))
+            .build();
+        let second = TaskContext::builder()
+            .name("Second")
+            .pins_used(vec![4])
+            .pin_modes(BTreeMap::from([(4, AccessMode::Shared)]))
+            .build();
+
+        let mut table = OwnershipTable::new();
+        table.claim(&first)?;
+        table.claim(&second)?;
+
+        // Pin 4 is still shared with the second task, so only pin 2 is freed.
+        assert_eq!(table.release_all(*first.id()), vec![TaskResource::Pin(2)]);
+        assert_eq!(table.release_all(*first.id()), vec![]);
+        assert_eq!(table.release_all(*second.id()), vec![TaskResource::Pin(4)]);
+        assert!(table.owners(TaskResource::Pin(4)).is_empty());
+        Ok(())
+    }
+}
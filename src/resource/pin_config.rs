@@ -0,0 +1,155 @@
+use std::fmt;
+
+use esp_idf_hal::gpio::{DriveStrength, Level, Pull};
+use getset::{Getters, Setters};
+use typed_builder::TypedBuilder;
+
+/// Which internal resistors pull a pin towards a level while nothing drives it.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PullMode {
+    /// Neither resistor is enabled, so the pin floats.
+    #[default]
+    Floating,
+    /// The pin is pulled up to a high level.
+    Up,
+    /// The pin is pulled down to a low level.
+    Down,
+    /// Both resistors are enabled, which holds the pin at around half the supply.
+    UpDown,
+}
+
+impl From<PullMode> for Pull {
+    fn from(pull: PullMode) -> Self {
+        match pull {
+            PullMode::Floating => Pull::Floating,
+            PullMode::Up => Pull::Up,
+            PullMode::Down => Pull::Down,
+            PullMode::UpDown => Pull::UpDown,
+        }
+    }
+}
+
+impl fmt::Display for PullMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Floating => "floating",
+            Self::Up => "pull-up",
+            Self::Down => "pull-down",
+            Self::UpDown => "pull-up and pull-down",
+        })
+    }
+}
+
+/// How much current a pin can source or sink while it is driven.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DriveCapability {
+    /// Around 5 mA.
+    #[cfg_attr(feature = "serde", serde(rename = "5mA"))]
+    I5mA,
+    /// Around 10 mA.
+    #[cfg_attr(feature = "serde", serde(rename = "10mA"))]
+    I10mA,
+    /// Around 20 mA, which pins are driven with unless told otherwise.
+    #[cfg_attr(feature = "serde", serde(rename = "20mA"))]
+    I20mA,
+    /// Around 40 mA.
+    #[cfg_attr(feature = "serde", serde(rename = "40mA"))]
+    I40mA,
+}
+
+impl From<DriveCapability> for DriveStrength {
+    fn from(capability: DriveCapability) -> Self {
+        match capability {
+            DriveCapability::I5mA => DriveStrength::I5mA,
+            DriveCapability::I10mA => DriveStrength::I10mA,
+            DriveCapability::I20mA => DriveStrength::I20mA,
+            DriveCapability::I40mA => DriveStrength::I40mA,
+        }
+    }
+}
+
+impl fmt::Display for DriveCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::I5mA => "5 mA",
+            Self::I10mA => "10 mA",
+            Self::I20mA => "20 mA",
+            Self::I40mA => "40 mA",
+        })
+    }
+}
+
+/// How the electrical side of a pin is set up when a task acquires it.
+///
+/// Every task holding the same pin must agree on its configuration, as they all
+/// see the same pin. Anything that isn't given is left as the chip resets it.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Getters, Setters, TypedBuilder)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct PinConfig {
+    /// Which internal resistors pull the pin while it is read.
+    #[getset(get = "pub", set = "pub")]
+    #[builder(default)]
+    pull: PullMode,
+
+    /// Whether the pin only ever pulls low while it is driven, and is left
+    /// floating when driven high.
+    #[getset(get = "pub", set = "pub")]
+    #[builder(default)]
+    open_drain: bool,
+
+    /// How much current the pin can drive, if not the default.
+    #[getset(get = "pub", set = "pub")]
+    #[builder(default, setter(strip_option))]
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    drive: Option<DriveCapability>,
+
+    /// The level that the pin is driven to as soon as it is first acquired,
+    /// before any step writes to it.
+    #[getset(get = "pub", set = "pub")]
+    #[builder(default, setter(strip_option))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_serializing_if = "Option::is_none",
+            with = "crate::config::optional_level"
+        )
+    )]
+    initial_level: Option<Level>,
+}
+
+impl PinConfig {
+    /// Whether the pin is set up just as the chip resets it.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for PinConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![self.pull.to_string()];
+        if self.open_drain {
+            parts.push("open-drain".to_string());
+        }
+        if let Some(drive) = self.drive {
+            parts.push(format!("driving {drive}"));
+        }
+        if let Some(level) = self.initial_level {
+            parts.push(format!(
+                "starting {}",
+                if level == Level::High { "high" } else { "low" }
+            ));
+        }
+
+        f.write_str(&parts.join(", "))
+    }
+}
@@ -0,0 +1,347 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use esp_idf_hal::{
+    gpio::{
+        AnyIOPin, AnyInputPin, DriveStrength, Input, InputOutput, InterruptType, Level, Output,
+        PinDriver, Pull,
+    },
+    peripheral::PeripheralRef,
+    sys::{gpio_get_level, EspError},
+};
+
+use super::{PinConfig, PinWatch};
+use crate::scheduler::waker;
+
+/// The direction that a pin is configured in.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum PinMode {
+    /// The pin has not been used yet, so it has not been configured.
+    #[default]
+    Disabled,
+    /// The pin can only be read.
+    Input,
+    /// The pin can only be driven.
+    Output,
+    /// The pin can be both read and driven, reading back the level it is driven to.
+    InputOutput,
+}
+
+impl PinMode {
+    /// The mode that can do everything both modes can.
+    pub fn join(self, other: PinMode) -> PinMode {
+        match (self, other) {
+            (Self::Disabled, mode) | (mode, Self::Disabled) => mode,
+            (a, b) if a == b => a,
+            _ => Self::InputOutput,
+        }
+    }
+
+    /// Whether the level of a pin in this mode can be read.
+    pub fn can_read(self) -> bool {
+        matches!(self, Self::Input | Self::InputOutput)
+    }
+
+    /// Whether a pin in this mode can be driven to a level.
+    pub fn can_write(self) -> bool {
+        matches!(self, Self::Output | Self::InputOutput)
+    }
+}
+
+/// The driver of a pin, in whichever mode it is configured in.
+///
+/// Input-only pins have no output driver, so they are kept apart and can only
+/// ever be configured as an input.
+enum Driver<'a> {
+    Disabled(PeripheralRef<'a, AnyIOPin>),
+    Input(PinDriver<'a, AnyIOPin, Input>),
+    Output(PinDriver<'a, AnyIOPin, Output>),
+    InputOutput(PinDriver<'a, AnyIOPin, InputOutput>),
+    DisabledInputOnly(PeripheralRef<'a, AnyInputPin>),
+    InputOnly(PinDriver<'a, AnyInputPin, Input>),
+}
+
+/// A GPIO pin along with the driver that it is configured with.
+///
+/// The driver is kept between steps, so a pin is only reconfigured when it is
+/// used in a way that its current mode doesn't allow. A pin that is both read
+/// and written becomes an input-output pin, rather than flipping between being
+/// an input and an output, and it keeps its level and interrupt when it does.
+pub struct CachedPin<'a> {
+    /// The number of the pin.
+    number: i32,
+    /// How the pin is set up, which is applied every time it is reconfigured.
+    config: PinConfig,
+    /// The driver of the pin, which is only missing if reconfiguring it failed.
+    driver: Option<Driver<'a>>,
+    /// The level that the pin was last driven to.
+    level: Option<Level>,
+    /// The watch that the interrupt handler of the pin records changes to.
+    watch: Option<Arc<PinWatch>>,
+    /// How many times the pin has been reconfigured.
+    reconfigurations: usize,
+}
+
+impl<'a> CachedPin<'a> {
+    /// Create a new pin that has not been configured yet.
+    pub fn new(number: i32, pin: PeripheralRef<'a, AnyIOPin>) -> Self {
+        Self::with_driver(number, Driver::Disabled(pin))
+    }
+
+    /// Create a new pin that has no output driver, so it can only be read.
+    pub fn input_only(number: i32, pin: PeripheralRef<'a, AnyInputPin>) -> Self {
+        Self::with_driver(number, Driver::DisabledInputOnly(pin))
+    }
+
+    fn with_driver(number: i32, driver: Driver<'a>) -> Self {
+        Self {
+            number,
+            config: PinConfig::default(),
+            driver: Some(driver),
+            level: None,
+            watch: None,
+            reconfigurations: 0,
+        }
+    }
+
+    /// The number of the pin.
+    pub fn number(&self) -> i32 {
+        self.number
+    }
+
+    /// The mode that the pin is currently configured in.
+    pub fn mode(&self) -> PinMode {
+        match self.driver {
+            Some(Driver::Input(_) | Driver::InputOnly(_)) => PinMode::Input,
+            Some(Driver::Output(_)) => PinMode::Output,
+            Some(Driver::InputOutput(_)) => PinMode::InputOutput,
+            Some(Driver::Disabled(_) | Driver::DisabledInputOnly(_)) | None => PinMode::Disabled,
+        }
+    }
+
+    /// How the pin is set up.
+    pub fn config(&self) -> PinConfig {
+        self.config
+    }
+
+    /// How many times the pin has been reconfigured, which is at most twice as a
+    /// pin never goes back to a mode that can do less, unless its configuration
+    /// changes whether it is open-drain.
+    pub fn reconfigurations(&self) -> usize {
+        self.reconfigurations
+    }
+
+    /// Read the level of the pin, configuring it as an input if it can't be read yet.
+    pub fn read(&mut self) -> anyhow::Result<Level> {
+        self.configure(PinMode::Input)?;
+        match &self.driver {
+            Some(Driver::Input(driver)) => Ok(driver.get_level()),
+            Some(Driver::InputOutput(driver)) => Ok(driver.get_level()),
+            Some(Driver::InputOnly(driver)) => Ok(driver.get_level()),
+            _ => unreachable!("The pin was just configured to be read."),
+        }
+    }
+
+    /// Drive the pin to a level, configuring it as an output if it can't be driven yet.
+    pub fn write(&mut self, level: Level) -> anyhow::Result<()> {
+        self.configure(PinMode::Output)?;
+        match &mut self.driver {
+            Some(Driver::Output(driver)) => driver.set_level(level)?,
+            Some(Driver::InputOutput(driver)) => driver.set_level(level)?,
+            _ => unreachable!("The pin was just configured to be written."),
+        }
+
+        self.level = Some(level);
+        Ok(())
+    }
+
+    /// Set up the pin in the given way, applying it to the driver straight away if
+    /// the pin has already been configured.
+    pub fn set_config(&mut self, config: PinConfig) -> anyhow::Result<()> {
+        if config == self.config {
+            return Ok(());
+        }
+
+        let rebuild = config.open_drain() != self.config.open_drain();
+        self.config = config;
+        match self.mode() {
+            PinMode::Disabled => Ok(()),
+            mode if rebuild && mode.can_write() => self.rebuild(mode),
+            _ => self.apply(),
+        }
+    }
+
+    /// Drive the pin to the initial level of its configuration, unless it has
+    /// already been driven to a level.
+    pub fn drive_initial_level(&mut self) -> anyhow::Result<()> {
+        match (self.level, self.config.initial_level()) {
+            (None, Some(level)) => self.write(*level),
+            _ => Ok(()),
+        }
+    }
+
+    /// Forget the level that the pin was last driven to, so that the next task to
+    /// drive it starts it at the initial level of its own configuration.
+    ///
+    /// The pin itself is left as it is until then.
+    pub fn forget_level(&mut self) {
+        self.level = None;
+    }
+
+    /// Record every change in the level of the pin on the watch, from inside of an
+    /// interrupt handler, configuring it as an input if it can't be read yet.
+    pub fn watch(&mut self, watch: Arc<PinWatch>) -> anyhow::Result<()> {
+        self.configure(PinMode::Input)?;
+        self.watch = Some(watch);
+        self.subscribe()
+    }
+
+    /// Make sure the pin can be used in the given mode, keeping what it can
+    /// already do.
+    fn configure(&mut self, mode: PinMode) -> anyhow::Result<()> {
+        let current = self.mode();
+        let wanted = current.join(mode);
+        if wanted == current {
+            return Ok(());
+        }
+
+        self.rebuild(wanted)
+    }
+
+    /// Whether the pin has no output driver, so it can only be read.
+    pub fn is_input_only(&self) -> bool {
+        matches!(
+            self.driver,
+            Some(Driver::DisabledInputOnly(_) | Driver::InputOnly(_))
+        )
+    }
+
+    /// Replace the driver with one in the given mode.
+    ///
+    /// Changing the mode of a driver resets the pin, so its configuration, the
+    /// level it was driven to and its interrupt are restored afterwards.
+    fn rebuild(&mut self, mode: PinMode) -> anyhow::Result<()> {
+        if self.is_input_only() && mode.can_write() {
+            bail!("Pin {} is input-only, so it can't be driven!", self.number);
+        }
+
+        let driver = self.driver.take().context(format!(
+            "Pin {} was lost after it failed to be reconfigured!",
+            self.number
+        ))?;
+        let open_drain = *self.config.open_drain();
+        let driver = match driver {
+            Driver::Disabled(pin) => match (mode, open_drain) {
+                (PinMode::Input, _) => PinDriver::input(pin).map(Driver::Input),
+                (PinMode::Output, false) => PinDriver::output(pin).map(Driver::Output),
+                (PinMode::Output, true) => PinDriver::output_od(pin).map(Driver::Output),
+                (_, false) => PinDriver::input_output(pin).map(Driver::InputOutput),
+                (_, true) => PinDriver::input_output_od(pin).map(Driver::InputOutput),
+            },
+            Driver::Input(driver) => Self::convert(driver, mode, open_drain),
+            Driver::Output(driver) => Self::convert(driver, mode, open_drain),
+            Driver::InputOutput(driver) => Self::convert(driver, mode, open_drain),
+            Driver::DisabledInputOnly(pin) => PinDriver::input(pin).map(Driver::InputOnly),
+            Driver::InputOnly(driver) => Ok(Driver::InputOnly(driver)),
+        }
+        .context(format!(
+            "Could not configure pin {} as {mode:?}!",
+            self.number
+        ))?;
+
+        self.driver = Some(driver);
+        self.reconfigurations += 1;
+        self.apply()?;
+
+        if let Some(level) = self.level.filter(|_| mode.can_write()) {
+            self.write(level)?;
+        }
+        if self.watch.is_some() && mode.can_read() {
+            self.subscribe()?;
+        }
+        Ok(())
+    }
+
+    /// Change the mode of a driver that has already been configured.
+    fn convert<M>(
+        driver: PinDriver<'a, AnyIOPin, M>,
+        mode: PinMode,
+        open_drain: bool,
+    ) -> Result<Driver<'a>, EspError> {
+        match (mode, open_drain) {
+            (PinMode::Input, _) => driver.into_input().map(Driver::Input),
+            (PinMode::Output, false) => driver.into_output().map(Driver::Output),
+            (PinMode::Output, true) => driver.into_output_od().map(Driver::Output),
+            (_, false) => driver.into_input_output().map(Driver::InputOutput),
+            (_, true) => driver.into_input_output_od().map(Driver::InputOutput),
+        }
+    }
+
+    /// Apply the pull and drive strength of the configuration to the driver.
+    ///
+    /// Pulls only matter while a pin is read, and drive strength while it is
+    /// driven, so each is only applied in the modes that use it.
+    fn apply(&mut self) -> anyhow::Result<()> {
+        let pull = Pull::from(*self.config.pull());
+        let drive = self.config.drive().map(DriveStrength::from);
+        match &mut self.driver {
+            Some(Driver::Input(driver)) => driver.set_pull(pull)?,
+            Some(Driver::Output(driver)) => {
+                if let Some(drive) = drive {
+                    driver.set_drive_strength(drive)?;
+                }
+            }
+            Some(Driver::InputOutput(driver)) => {
+                driver.set_pull(pull)?;
+                if let Some(drive) = drive {
+                    driver.set_drive_strength(drive)?;
+                }
+            }
+            // Input-only pins have no pull resistors either.
+            Some(Driver::InputOnly(_)) if pull != Pull::Floating => {
+                bail!("Pin {} is input-only, so it can't be pulled!", self.number)
+            }
+            Some(Driver::InputOnly(_) | Driver::Disabled(_) | Driver::DisabledInputOnly(_))
+            | None => (),
+        }
+        Ok(())
+    }
+
+    /// Subscribe the interrupt handler of the pin to any edge, recording each
+    /// change on the watch and waking the scheduler.
+    fn subscribe(&mut self) -> anyhow::Result<()> {
+        let (Some(watch), number) = (self.watch.clone(), self.number) else {
+            return Ok(());
+        };
+        let callback = move || {
+            // Safety: Reading the level of a pin is safe from inside an ISR.
+            watch.interrupt((unsafe { gpio_get_level(number) } != 0).into());
+            waker::wake();
+        };
+
+        // Safety: The callback runs in an ISR, so it only reads the level of the
+        // pin, stores to atomics and signals the scheduler, which only takes the
+        // ISR-safe critical section of esp-idf-hal.
+        unsafe {
+            match &mut self.driver {
+                Some(Driver::Input(driver)) => {
+                    driver.set_interrupt_type(InterruptType::AnyEdge)?;
+                    driver.subscribe(callback)?;
+                    driver.enable_interrupt()?;
+                }
+                Some(Driver::InputOutput(driver)) => {
+                    driver.set_interrupt_type(InterruptType::AnyEdge)?;
+                    driver.subscribe(callback)?;
+                    driver.enable_interrupt()?;
+                }
+                Some(Driver::InputOnly(driver)) => {
+                    driver.set_interrupt_type(InterruptType::AnyEdge)?;
+                    driver.subscribe(callback)?;
+                    driver.enable_interrupt()?;
+                }
+                _ => unreachable!("The pin was just configured to be read."),
+            }
+        }
+        Ok(())
+    }
+}
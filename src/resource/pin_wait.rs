@@ -0,0 +1,184 @@
+use std::{task::Poll, time};
+
+use super::{Request, RequestResolver, RequestResult, ResourceManager};
+use crate::task::TaskContext;
+
+/// Resolves requests that wait for a GPIO pin to reach a level or see an edge.
+///
+/// Pins are watched through GPIO interrupts, so a waiting task costs nothing
+/// until either the pin changes or its timeout expires.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PinWaitResolver;
+
+impl RequestResolver for PinWaitResolver {
+    fn poll(
+        &mut self,
+        request: &Request,
+        context: &TaskContext,
+        manager: &mut ResourceManager,
+    ) -> Poll<RequestResult> {
+        let number = match request {
+            Request::WaitLevel(number, ..) | Request::WaitEdge(number, ..) => *number,
+            _ => return Poll::Pending,
+        };
+
+        if let Some(watch) = manager.watched(number) {
+            if watch.take_fired() {
+                if let Err(error) = manager.rearm(number) {
+                    log::warn!("Could not re-enable the interrupt on GPIO Pin {number}: {error:?}");
+                }
+            }
+
+            let reached = match request {
+                Request::WaitLevel(_, level, _) => watch.level() == *level,
+                Request::WaitEdge(_, edge, _, seen) => watch.edges(*edge) != *seen,
+                _ => false,
+            };
+
+            if reached {
+                return Poll::Ready(RequestResult::Level(watch.level()));
+            }
+        }
+
+        match self.deadline(request, context) {
+            Some(deadline) if deadline <= time::Instant::now() => {
+                Poll::Ready(RequestResult::TimedOut)
+            }
+            _ => Poll::Pending,
+        }
+    }
+
+    fn deadline(&self, request: &Request, context: &TaskContext) -> Option<time::Instant> {
+        match request {
+            Request::WaitLevel(_, _, Some(ms)) | Request::WaitEdge(_, _, Some(ms), _) => {
+                Some(*context.last_run_timestamp() + time::Duration::from_millis(*ms as u64))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{task::Poll, thread, time};
+
+    use anyhow::Context;
+    use esp_idf_hal::gpio::Level;
+
+    use super::PinWaitResolver;
+    use crate::{
+        resource::{Edge, Request, RequestResolver, RequestResult, ResourceManager},
+        scheduler::waker,
+        task::{Register, TaskContext, TaskStep, Value},
+        testing,
+    };
+
+    testing::tests!(wakes_on_level, wakes_on_edge, wakes_on_any_edge, times_out);
+
+    const PIN: i32 = 4;
+
+    /// Run a step that waits on the pin, which starts out low, returning the
+    /// request that it blocks on.
+    fn wait(step: TaskStep) -> anyhow::Result<(ResourceManager<'static>, TaskContext, Request)> {
+        let mut manager = ResourceManager::for_tests();
+        let mut context = TaskContext::builder()
+            .name("Test")
+            .pins_used(vec![PIN])
+            .build();
+
+        manager.simulate_watch(PIN, Level::Low)?;
+        let request = step
+            .execute(&mut context, &mut manager)?
+            .context("Waiting on a pin should block the task!")?;
+        Ok((manager, context, request))
+    }
+
+    fn wakes_on_level() -> anyhow::Result<()> {
+        let (mut manager, mut context, request) = wait(TaskStep::WaitLevel(
+            PIN,
+            Level::High,
+            None,
+            Some(Register::Bool(0)),
+        ))?;
+        let mut resolver = PinWaitResolver;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Pending
+        );
+
+        waker::take();
+        manager.simulate_pin_change(PIN, Level::High)?;
+        assert!(
+            waker::take(),
+            "The scheduler should be woken by the change."
+        );
+        let result = resolver.poll(&request, &context, &mut manager);
+        assert_eq!(result, Poll::Ready(RequestResult::Level(Level::High)));
+
+        // The level is stored in the register chosen by the step.
+        if let Poll::Ready(result) = result {
+            context.write_back(result);
+        }
+        assert_eq!(
+            context.registers().get(Register::Bool(0))?,
+            Value::Bool(true)
+        );
+        Ok(())
+    }
+
+    fn wakes_on_edge() -> anyhow::Result<()> {
+        let (mut manager, context, request) =
+            wait(TaskStep::WaitEdge(PIN, Edge::Falling, None, None))?;
+        let mut resolver = PinWaitResolver;
+
+        manager.simulate_pin_change(PIN, Level::High)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Pending
+        );
+
+        manager.simulate_pin_change(PIN, Level::Low)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Ready(RequestResult::Level(Level::Low))
+        );
+        Ok(())
+    }
+
+    fn wakes_on_any_edge() -> anyhow::Result<()> {
+        let (mut manager, context, request) = wait(TaskStep::WaitEdge(PIN, Edge::Any, None, None))?;
+        let mut resolver = PinWaitResolver;
+
+        // Staying at the same level is not an edge.
+        manager.simulate_pin_change(PIN, Level::Low)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Pending
+        );
+
+        manager.simulate_pin_change(PIN, Level::High)?;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Ready(RequestResult::Level(Level::High))
+        );
+        Ok(())
+    }
+
+    fn times_out() -> anyhow::Result<()> {
+        let (mut manager, context, request) =
+            wait(TaskStep::WaitLevel(PIN, Level::High, Some(10), None))?;
+        let mut resolver = PinWaitResolver;
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Pending
+        );
+        assert!(resolver.deadline(&request, &context).is_some());
+
+        thread::sleep(time::Duration::from_millis(20));
+        assert_eq!(
+            resolver.poll(&request, &context, &mut manager),
+            Poll::Ready(RequestResult::TimedOut)
+        );
+        Ok(())
+    }
+}
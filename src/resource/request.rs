@@ -1,8 +1,69 @@
+use std::time;
+
+use esp_idf_hal::gpio::Level;
+
+use super::{Edge, WaitMode};
+
 /// Represents an I/O Request made by a task.
 ///
 /// Tasks that make a request will be blocked until that request
 /// has been completed.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Request {
     Yield(usize),
+    /// Give up the current slot without any delay.
+    ///
+    /// This is handled by the scheduler directly and never blocks the task.
+    YieldNow,
+    /// Sleep until an absolute point in time.
+    SleepUntil(time::Instant),
+    /// Wait for a pin to reach a level, with an optional timeout in milliseconds.
+    WaitLevel(i32, Level, Option<usize>),
+    /// Wait for a pin to see an edge, with an optional timeout in milliseconds.
+    ///
+    /// The last value holds the number of such edges already seen when the
+    /// request was made, so that only new edges complete it.
+    WaitEdge(i32, Edge, Option<usize>, u32),
+    /// Send a value to the named channel, waiting for room if it is full.
+    Send(String, i32),
+    /// Receive a value from the named channel, waiting for one if it is empty.
+    Recv(String),
+    /// Acquire a permit from the named semaphore or mutex, waiting for one to be released.
+    Acquire(String),
+    /// Wait for the bits in the mask to be set in the named event group, optionally
+    /// clearing them once the wait is over.
+    WaitBits(String, u32, WaitMode, bool),
+}
+
+/// Represents the kind of a request, without any of the data it carries.
+///
+/// Resolvers are registered with the scheduler per kind of request.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum RequestKind {
+    Yield,
+    YieldNow,
+    SleepUntil,
+    WaitLevel,
+    WaitEdge,
+    Send,
+    Recv,
+    Acquire,
+    WaitBits,
+}
+
+impl Request {
+    /// The kind of this request, used to look up the resolver responsible for it.
+    pub fn kind(&self) -> RequestKind {
+        match self {
+            Self::Yield(_) => RequestKind::Yield,
+            Self::YieldNow => RequestKind::YieldNow,
+            Self::SleepUntil(_) => RequestKind::SleepUntil,
+            Self::WaitLevel(..) => RequestKind::WaitLevel,
+            Self::WaitEdge(..) => RequestKind::WaitEdge,
+            Self::Send(..) => RequestKind::Send,
+            Self::Recv(_) => RequestKind::Recv,
+            Self::Acquire(_) => RequestKind::Acquire,
+            Self::WaitBits(..) => RequestKind::WaitBits,
+        }
+    }
 }
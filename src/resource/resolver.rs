@@ -0,0 +1,56 @@
+use std::{task::Poll, time};
+
+use esp_idf_hal::gpio::Level;
+
+use super::{Request, ResourceManager};
+use crate::task::TaskContext;
+
+/// Represents the value produced by a request once it has been completed.
+///
+/// The scheduler writes this back into the context of the task that made the
+/// request before the task is allowed to run again.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RequestResult {
+    /// The request completed without producing a value.
+    Done,
+    /// The request produced the level of a pin.
+    Level(Level),
+    /// The request produced a value, such as one received from a channel.
+    Value(i32),
+    /// The request produced the bits of an event group at the time it was completed.
+    Bits(u32),
+    /// The request was not completed before its timeout expired.
+    TimedOut,
+    /// The task slept until its deadline, and was woken this late after it.
+    Slept(time::Duration),
+}
+
+/// Decides when a blocking request made by a task has been completed.
+///
+/// Resolvers are registered with the scheduler for a single kind of request, so
+/// new blocking operations can be added without touching the scheduler loop.
+pub trait RequestResolver {
+    /// Check whether the request made by the task with the given context has
+    /// been completed, returning its result if so.
+    fn poll(
+        &mut self,
+        request: &Request,
+        context: &TaskContext,
+        manager: &mut ResourceManager,
+    ) -> Poll<RequestResult>;
+
+    /// Notify the resolver that a request it completed has been written back
+    /// into the context of the task that made it.
+    fn complete(&mut self, _request: &Request, _context: &TaskContext) {}
+
+    /// Called once every blocked task has been polled, to apply any effects that
+    /// must only happen after all of the tasks have seen the same state.
+    fn finish(&mut self, _manager: &mut ResourceManager) {}
+
+    /// The earliest instant at which the request could be completed, if known.
+    ///
+    /// This is used to sleep instead of polling when there is nothing else to do.
+    fn deadline(&self, _request: &Request, _context: &TaskContext) -> Option<time::Instant> {
+        None
+    }
+}
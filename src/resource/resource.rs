@@ -1,8 +1,19 @@
+use std::fmt;
+
 /// Represents a possible resource that could be assigned to a task.
 ///
 /// This resource can be anything I/O bound, such as a Pin, File, or
 /// anything else.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum TaskResource {
     /// A pin is a resource represented by its pin number.
     Pin(i32),
 }
+
+impl fmt::Display for TaskResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pin(pin) => write!(f, "Pin {pin}"),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context;
+use esp_idf_hal::gpio::Level;
+
+use super::{Access, AccessMode, CachedPin, PinMode, PinWatch, TaskResource};
+
+/// A kind of resource that tasks acquire through the resource manager.
+pub trait ResourceKind {
+    /// What the manager holds for each resource of this kind.
+    type Target<'a>;
+}
+
+/// GPIO pins, which the manager holds along with the driver each is configured with.
+#[derive(Debug)]
+pub enum GpioPin {}
+
+impl ResourceKind for GpioPin {
+    type Target<'a> = CachedPin<'a>;
+}
+
+/// A handle to a resource that a task has acquired from the resource manager.
+///
+/// The handle borrows the resource from the manager, so nothing else can use it
+/// while the handle is alive, and it only allows what the mode that the task
+/// declared the resource in allows. The resource is given back to the manager
+/// when the handle is dropped.
+pub struct ResourceRef<'r, 'a, K: ResourceKind> {
+    /// The resource being held.
+    resource: TaskResource,
+    /// The id of the task holding the resource.
+    holder: uuid::Uuid,
+    /// The mode that the task declared the resource in.
+    mode: AccessMode,
+    /// The resource itself, as held by the manager.
+    target: &'r mut K::Target<'a>,
+    /// The resources that the manager has handed out, which this is removed from when dropped.
+    held: &'r mut HashMap<TaskResource, uuid::Uuid>,
+}
+
+impl<'r, 'a, K: ResourceKind> ResourceRef<'r, 'a, K> {
+    /// Hand out a resource, recording that the task holds it until the handle is dropped.
+    pub(super) fn new(
+        resource: TaskResource,
+        holder: uuid::Uuid,
+        mode: AccessMode,
+        target: &'r mut K::Target<'a>,
+        held: &'r mut HashMap<TaskResource, uuid::Uuid>,
+    ) -> Self {
+        held.insert(resource, holder);
+        Self {
+            resource,
+            holder,
+            mode,
+            target,
+            held,
+        }
+    }
+
+    /// The resource being held.
+    pub fn resource(&self) -> TaskResource {
+        self.resource
+    }
+
+    /// The id of the task holding the resource.
+    pub fn holder(&self) -> uuid::Uuid {
+        self.holder
+    }
+
+    /// The mode that the task declared the resource in.
+    pub fn mode(&self) -> AccessMode {
+        self.mode
+    }
+
+    /// Check that the mode that the resource is held in allows the access.
+    fn check(&self, access: Access) -> anyhow::Result<()> {
+        self.mode.allows(access).then_some(()).context(format!(
+            "Cannot {access} {} as it was declared as {}!",
+            self.resource, self.mode
+        ))
+    }
+}
+
+impl<'r, 'a> ResourceRef<'r, 'a, GpioPin> {
+    /// The number of the pin.
+    pub fn number(&self) -> i32 {
+        match self.resource {
+            TaskResource::Pin(number) => number,
+        }
+    }
+
+    /// The mode that the driver of the pin is currently configured in.
+    pub fn pin_mode(&self) -> PinMode {
+        self.target.mode()
+    }
+
+    /// Read the level of the pin, configuring it as an input if it can't be read yet.
+    pub fn read(&mut self) -> anyhow::Result<Level> {
+        self.check(Access::Read)?;
+        self.target.read()
+    }
+
+    /// Drive the pin to a level, configuring it as an output if it can't be driven yet.
+    pub fn write(&mut self, level: Level) -> anyhow::Result<()> {
+        self.check(Access::Write)?;
+        self.target.write(level)
+    }
+
+    /// Record every change in the level of the pin on the watch, using a GPIO interrupt.
+    pub fn watch(&mut self, watch: Arc<PinWatch>) -> anyhow::Result<()> {
+        self.check(Access::Read)?;
+        self.target.watch(watch)
+    }
+}
+
+impl<'r, 'a, K: ResourceKind> Drop for ResourceRef<'r, 'a, K> {
+    fn drop(&mut self) {
+        self.held.remove(&self.resource);
+    }
+}
@@ -0,0 +1,126 @@
+/// A counting semaphore or mutex owned by the scheduler.
+///
+/// Every permit that is handed out is recorded against the id of the task that
+/// acquired it, so that a task which exits or faults while still holding any
+/// permits can have them released automatically.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Semaphore {
+    /// The total number of permits that can be held at once.
+    permits: usize,
+    /// The ids of the tasks holding a permit, one entry per permit held.
+    holders: Vec<uuid::Uuid>,
+    /// Whether this is a mutex, which only ever has a single owner.
+    mutex: bool,
+}
+
+impl Semaphore {
+    /// Create a new counting semaphore with the given number of permits.
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits,
+            holders: vec![],
+            mutex: false,
+        }
+    }
+
+    /// Create a new mutex, which is a semaphore with a single permit.
+    pub fn mutex() -> Self {
+        Self {
+            mutex: true,
+            ..Self::new(1)
+        }
+    }
+
+    /// Try to take a permit for the task with the given id.
+    ///
+    /// Returns false without taking a permit if none are available.
+    pub fn try_acquire(&mut self, holder: uuid::Uuid) -> bool {
+        if self.available() == 0 {
+            return false;
+        }
+
+        self.holders.push(holder);
+        true
+    }
+
+    /// Give back a single permit held by the task with the given id.
+    ///
+    /// Returns false if the task does not hold a permit.
+    pub fn release(&mut self, holder: uuid::Uuid) -> bool {
+        match self.holders.iter().position(|id| *id == holder) {
+            Some(index) => {
+                self.holders.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Give back every permit held by the task with the given id.
+    ///
+    /// Returns the number of permits that were released.
+    pub fn release_all(&mut self, holder: uuid::Uuid) -> usize {
+        let held = self.holders.len();
+        self.holders.retain(|id| *id != holder);
+        held - self.holders.len()
+    }
+
+    /// The total number of permits that can be held at once.
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// The number of permits that can still be acquired.
+    pub fn available(&self) -> usize {
+        self.permits - self.holders.len()
+    }
+
+    /// The ids of the tasks holding a permit, one entry per permit held.
+    pub fn holders(&self) -> &[uuid::Uuid] {
+        &self.holders
+    }
+
+    /// Whether this is a mutex rather than a counting semaphore.
+    pub fn is_mutex(&self) -> bool {
+        self.mutex
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Semaphore;
+    use crate::testing;
+
+    testing::tests!(hands_out_every_permit, releases_every_permit_of_a_holder);
+
+    fn hands_out_every_permit() -> anyhow::Result<()> {
+        let (first, second) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let mut semaphore = Semaphore::new(2);
+        assert!(semaphore.try_acquire(first));
+        assert!(semaphore.try_acquire(second));
+        assert!(!semaphore.try_acquire(first));
+        assert_eq!(semaphore.available(), 0);
+
+        assert!(semaphore.release(second));
+        assert!(!semaphore.release(second));
+        assert_eq!(semaphore.available(), 1);
+
+        let mut mutex = Semaphore::mutex();
+        assert!(mutex.try_acquire(first));
+        assert!(!mutex.try_acquire(second));
+        Ok(())
+    }
+
+    fn releases_every_permit_of_a_holder() -> anyhow::Result<()> {
+        let (exiting, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let mut semaphore = Semaphore::new(3);
+        semaphore.try_acquire(exiting);
+        semaphore.try_acquire(other);
+        semaphore.try_acquire(exiting);
+
+        assert_eq!(semaphore.release_all(exiting), 2);
+        assert_eq!(semaphore.holders(), &[other]);
+        assert_eq!(semaphore.release_all(exiting), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use std::{task::Poll, time};
+
+use super::{Request, RequestResolver, RequestResult, ResourceManager};
+use crate::task::TaskContext;
+
+/// Resolves requests that only wait for an amount of time to pass.
+///
+/// The result records how late the task was woken after its deadline.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimerResolver;
+
+impl RequestResolver for TimerResolver {
+    fn poll(
+        &mut self,
+        request: &Request,
+        context: &TaskContext,
+        _manager: &mut ResourceManager,
+    ) -> Poll<RequestResult> {
+        let now = time::Instant::now();
+        match self.deadline(request, context) {
+            Some(deadline) if deadline <= now => Poll::Ready(RequestResult::Slept(now - deadline)),
+            _ => Poll::Pending,
+        }
+    }
+
+    fn deadline(&self, request: &Request, context: &TaskContext) -> Option<time::Instant> {
+        match request {
+            Request::Yield(ms) => {
+                Some(*context.last_run_timestamp() + time::Duration::from_millis(*ms as u64))
+            }
+            Request::SleepUntil(deadline) => Some(*deadline),
+            _ => None,
+        }
+    }
+}
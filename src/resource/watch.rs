@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use esp_idf_hal::gpio::Level;
+
+/// Represents a change in the level of a pin that a task can wait for.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Edge {
+    /// The pin went from low to high.
+    Rising,
+    /// The pin went from high to low.
+    Falling,
+    /// The pin changed level in either direction.
+    Any,
+}
+
+/// The state of a pin that is being watched through a GPIO interrupt.
+///
+/// This is shared between the resource manager and the interrupt handler of the
+/// pin, so it only uses atomics and can safely be updated from an ISR.
+#[derive(Debug)]
+pub struct PinWatch {
+    /// The level of the pin after its last change.
+    level: AtomicBool,
+    /// The number of rising edges seen since the pin started being watched.
+    rising_edges: AtomicU32,
+    /// The number of falling edges seen since the pin started being watched.
+    falling_edges: AtomicU32,
+    /// Whether the interrupt has fired and must be enabled again.
+    fired: AtomicBool,
+}
+
+impl PinWatch {
+    /// Create a new watch for a pin that currently has the given level.
+    pub fn new(level: Level) -> Self {
+        Self {
+            level: AtomicBool::new(level.into()),
+            rising_edges: AtomicU32::new(0),
+            falling_edges: AtomicU32::new(0),
+            fired: AtomicBool::new(false),
+        }
+    }
+
+    /// Record that the pin now has the given level, counting any edge this makes.
+    ///
+    /// This is used both by the interrupt handler and to simulate pin changes.
+    pub fn record(&self, level: Level) {
+        match (self.level.swap(level.into(), Ordering::AcqRel), level) {
+            (false, Level::High) => {
+                self.rising_edges.fetch_add(1, Ordering::AcqRel);
+            }
+            (true, Level::Low) => {
+                self.falling_edges.fetch_add(1, Ordering::AcqRel);
+            }
+            _ => (),
+        }
+    }
+
+    /// Record a level read from within the interrupt handler of the pin.
+    ///
+    /// ESP-IDF disables the interrupt after it fires, so this also marks the
+    /// watch as needing to be enabled again.
+    pub fn interrupt(&self, level: Level) {
+        self.record(level);
+        self.fired.store(true, Ordering::Release);
+    }
+
+    /// The level of the pin after its last change.
+    pub fn level(&self) -> Level {
+        self.level.load(Ordering::Acquire).into()
+    }
+
+    /// The number of edges of the given kind seen so far.
+    ///
+    /// This wraps around, so it should only ever be compared for equality.
+    pub fn edges(&self, edge: Edge) -> u32 {
+        let rising = self.rising_edges.load(Ordering::Acquire);
+        let falling = self.falling_edges.load(Ordering::Acquire);
+        match edge {
+            Edge::Rising => rising,
+            Edge::Falling => falling,
+            Edge::Any => rising.wrapping_add(falling),
+        }
+    }
+
+    /// Check whether the interrupt has fired since this was last called.
+    pub fn take_fired(&self) -> bool {
+        self.fired.swap(false, Ordering::AcqRel)
+    }
+}
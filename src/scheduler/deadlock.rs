@@ -0,0 +1,356 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use getset::Getters;
+
+use crate::{
+    resource::{Request, ResourceManager},
+    task::{Task, TaskPriority},
+};
+
+/// What the scheduler does when it finds tasks that are deadlocked.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum DeadlockResolution {
+    /// Log the deadlock, leaving the tasks blocked.
+    #[default]
+    Report,
+    /// Log the deadlock, then fault the lowest priority task in it so that the
+    /// locks it holds are released and the other tasks can carry on.
+    FaultLowestPriority,
+}
+
+/// A single task in a deadlock, along with the lock that it is waiting for.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
+pub struct Wait {
+    /// The unique id of the task.
+    #[getset(get = "pub")]
+    id: uuid::Uuid,
+    /// The friendly display name of the task.
+    #[getset(get = "pub")]
+    name: String,
+    /// The execution priority of the task.
+    #[getset(get = "pub")]
+    priority: TaskPriority,
+    /// The name of the semaphore or mutex that the task is waiting for, which is
+    /// held by the next task in the cycle.
+    #[getset(get = "pub")]
+    resource: String,
+}
+
+/// A cycle of tasks that are each waiting for a lock held by the next, so none
+/// of them can ever run again.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
+pub struct Deadlock {
+    /// The tasks in the cycle, where the last task waits for the first.
+    #[getset(get = "pub")]
+    cycle: Vec<Wait>,
+}
+
+impl Deadlock {
+    /// The task in the cycle with the lowest priority, which is the first of them
+    /// if several share the lowest priority.
+    pub fn lowest_priority(&self) -> &Wait {
+        self.cycle
+            .iter()
+            .min_by_key(|wait| wait.priority)
+            .expect("A deadlock always has at least one task in it.")
+    }
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Deadlock between {} task(s):", self.cycle.len())?;
+        for (index, wait) in self.cycle.iter().enumerate() {
+            let next = &self.cycle[(index + 1) % self.cycle.len()];
+            write!(
+                f,
+                "\n  - Task '{}' waits for '{}', which is held by task '{}'.",
+                wait.name, wait.resource, next.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A blocked task in the wait-for graph.
+#[derive(Debug)]
+struct Node {
+    name: String,
+    priority: TaskPriority,
+    /// Each lock that the task is waiting for, with the tasks holding it.
+    waits: Vec<(String, Vec<uuid::Uuid>)>,
+}
+
+/// Which blocked tasks are waiting for locks held by which other tasks.
+///
+/// A task waiting for a counting semaphore can carry on once any one of the
+/// tasks holding it lets go, so a cycle only counts as a deadlock when every
+/// holder of each lock in it is also stuck.
+#[derive(Debug)]
+pub struct WaitForGraph {
+    nodes: HashMap<uuid::Uuid, Node>,
+}
+
+impl WaitForGraph {
+    /// Build the graph for the given blocked tasks, from who holds each lock.
+    ///
+    /// Locks with permits still available are left out, as they are about to be
+    /// handed to a waiting task, as are locks that nobody holds.
+    pub fn new<'t>(
+        blocked_tasks: impl IntoIterator<Item = &'t Task>,
+        manager: &ResourceManager,
+    ) -> Self {
+        let nodes = blocked_tasks
+            .into_iter()
+            .filter_map(|task| {
+                let context = task.context();
+                let waits: Vec<_> = context
+                    .block_requests()
+                    .iter()
+                    .filter_map(|request| match request {
+                        Request::Acquire(name) => manager
+                            .semaphore(name)
+                            .filter(|semaphore| {
+                                semaphore.available() == 0 && !semaphore.holders().is_empty()
+                            })
+                            .map(|semaphore| (name.clone(), semaphore.holders().to_vec())),
+                        _ => None,
+                    })
+                    .collect();
+
+                (!waits.is_empty()).then(|| {
+                    let node = Node {
+                        name: context.name().clone(),
+                        priority: *context.priority(),
+                        waits,
+                    };
+                    (*context.id(), node)
+                })
+            })
+            .collect();
+
+        Self { nodes }
+    }
+
+    /// The ids of every task that can never run again.
+    ///
+    /// Every task that isn't waiting for a lock can eventually let go of the locks
+    /// it holds, as can any task whose locks each have a holder that can. Whatever
+    /// is left once nothing more can be let go of is deadlocked.
+    pub fn deadlocked(&self) -> HashSet<uuid::Uuid> {
+        let mut stuck: HashSet<_> = self.nodes.keys().copied().collect();
+
+        loop {
+            let free: Vec<_> = stuck
+                .iter()
+                .filter(|id| {
+                    self.nodes[id]
+                        .waits
+                        .iter()
+                        .all(|(_, holders)| holders.iter().any(|holder| !stuck.contains(holder)))
+                })
+                .copied()
+                .collect();
+
+            if free.is_empty() {
+                return stuck;
+            }
+            free.iter().for_each(|id| {
+                stuck.remove(id);
+            });
+        }
+    }
+
+    /// Find a deadlock that the given task is stuck in, if it is stuck.
+    ///
+    /// The task may not be part of the cycle itself, if it is only waiting for a
+    /// lock held by one of the tasks in the cycle.
+    pub fn find(&self, id: uuid::Uuid) -> Option<Deadlock> {
+        let stuck = self.deadlocked();
+        if !stuck.contains(&id) {
+            return None;
+        }
+
+        // Every stuck task waits for at least one other stuck task, so following
+        // those waits must come back around to a task already seen.
+        let mut path: Vec<Wait> = vec![];
+        let mut current = id;
+        loop {
+            if let Some(start) = path.iter().position(|wait| wait.id == current) {
+                return Some(Deadlock {
+                    cycle: path.split_off(start),
+                });
+            }
+
+            let node = &self.nodes[&current];
+            let (resource, next) = node
+                .waits
+                .iter()
+                .find_map(|(resource, holders)| {
+                    // Prefer another task over the task itself, which can also hold
+                    // permits of a counting semaphore that it waits for.
+                    let mut holders = holders.iter().filter(|holder| stuck.contains(holder));
+                    holders
+                        .clone()
+                        .find(|holder| **holder != current)
+                        .or_else(|| holders.next())
+                        .map(|holder| (resource, *holder))
+                })
+                .expect("A stuck task always waits for another stuck task.");
+
+            path.push(Wait {
+                id: current,
+                name: node.name.clone(),
+                priority: node.priority,
+                resource: resource.clone(),
+            });
+            current = next;
+        }
+    }
+
+    /// Find any deadlock among the tasks in the graph.
+    pub fn find_any(&self) -> Option<Deadlock> {
+        let stuck = self.deadlocked();
+        stuck.iter().next().and_then(|id| self.find(*id))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use anyhow::Context;
+
+    use super::WaitForGraph;
+    use crate::{
+        resource::{Request, ResourceManager, Semaphore},
+        task::{Shot, Task, TaskContext, TaskPriority, TaskStatus, TaskStep},
+        testing,
+    };
+
+    testing::tests!(
+        finds_two_task_cycles,
+        finds_three_task_cycles,
+        ignores_chains,
+        names_every_task_and_resource,
+    );
+
+    /// A task that holds the mutex `holds` and is blocked waiting for the mutex
+    /// `waits`, creating either of them if they don't exist yet.
+    pub fn blocked_task(
+        manager: &mut ResourceManager,
+        name: &str,
+        priority: TaskPriority,
+        holds: &str,
+        waits: &str,
+    ) -> anyhow::Result<Task> {
+        for mutex in [holds, waits] {
+            if manager.semaphore(mutex).is_none() {
+                manager.create_semaphore(mutex.into(), Semaphore::mutex())?;
+            }
+        }
+
+        let mut task = Task::builder()
+            .context(TaskContext::builder().name(name).priority(priority).build())
+            .steps(vec![TaskStep::Acquire(waits.into())])
+            .shots(Shot::Custom(1))
+            .build()?;
+        let context = task.context_mut();
+        let semaphore = manager.semaphore_mut(holds).context("Missing mutex!")?;
+        semaphore
+            .try_acquire(*context.id())
+            .then_some(())
+            .context(format!("Mutex {holds} is already held!"))?;
+        *context.state_mut() = TaskStatus::Blocked;
+        context
+            .block_requests_mut()
+            .push(Request::Acquire(waits.into()));
+        Ok(task)
+    }
+
+    /// The names of the tasks in the deadlock that a task is in, starting from it.
+    fn cycle(graph: &WaitForGraph, task: &Task) -> anyhow::Result<Vec<(String, String)>> {
+        let deadlock = graph
+            .find(*task.context().id())
+            .context("The task should be deadlocked!")?;
+        Ok(deadlock
+            .cycle()
+            .iter()
+            .map(|wait| (wait.name().clone(), wait.resource().clone()))
+            .collect())
+    }
+
+    fn finds_two_task_cycles() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        let tasks = [
+            blocked_task(&mut manager, "A", TaskPriority::Low, "m1", "m2")?,
+            blocked_task(&mut manager, "B", TaskPriority::Low, "m2", "m1")?,
+        ];
+        let graph = WaitForGraph::new(&tasks, &manager);
+
+        let pair = |name: &str, resource: &str| (name.to_string(), resource.to_string());
+        assert_eq!(
+            cycle(&graph, &tasks[0])?,
+            vec![pair("A", "m2"), pair("B", "m1")]
+        );
+        assert_eq!(
+            cycle(&graph, &tasks[1])?,
+            vec![pair("B", "m1"), pair("A", "m2")]
+        );
+        assert!(graph.find_any().is_some());
+        Ok(())
+    }
+
+    fn finds_three_task_cycles() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        let tasks = [
+            blocked_task(&mut manager, "A", TaskPriority::Low, "m1", "m2")?,
+            blocked_task(&mut manager, "B", TaskPriority::Low, "m2", "m3")?,
+            blocked_task(&mut manager, "C", TaskPriority::Low, "m3", "m1")?,
+        ];
+        let graph = WaitForGraph::new(&tasks, &manager);
+
+        let pair = |name: &str, resource: &str| (name.to_string(), resource.to_string());
+        assert_eq!(
+            cycle(&graph, &tasks[1])?,
+            vec![pair("B", "m3"), pair("C", "m1"), pair("A", "m2")]
+        );
+        assert_eq!(graph.deadlocked().len(), 3);
+        Ok(())
+    }
+
+    fn ignores_chains() -> anyhow::Result<()> {
+        // C holds m3 but isn't blocked, so it will let go of it and the rest follow.
+        let mut manager = ResourceManager::for_tests();
+        let tasks = [
+            blocked_task(&mut manager, "A", TaskPriority::Low, "m1", "m2")?,
+            blocked_task(&mut manager, "B", TaskPriority::Low, "m2", "m3")?,
+        ];
+        let free = blocked_task(&mut manager, "C", TaskPriority::Low, "m3", "m4")?;
+        let graph = WaitForGraph::new(&tasks, &manager);
+
+        assert!(graph.deadlocked().is_empty());
+        assert!(graph.find(*tasks[0].context().id()).is_none());
+        assert!(graph.find(*free.context().id()).is_none());
+        assert!(graph.find_any().is_none());
+        Ok(())
+    }
+
+    fn names_every_task_and_resource() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        let tasks = [
+            blocked_task(&mut manager, "Sensor", TaskPriority::Low, "i2c", "spi")?,
+            blocked_task(&mut manager, "Display", TaskPriority::Low, "spi", "i2c")?,
+        ];
+        let deadlock = WaitForGraph::new(&tasks, &manager)
+            .find(*tasks[0].context().id())
+            .context("The tasks should be deadlocked!")?;
+        assert_eq!(
+            deadlock.to_string(),
+            "Deadlock between 2 task(s):\n  \
+             - Task 'Sensor' waits for 'spi', which is held by task 'Display'.\n  \
+             - Task 'Display' waits for 'i2c', which is held by task 'Sensor'."
+        );
+        Ok(())
+    }
+}
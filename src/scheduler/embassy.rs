@@ -0,0 +1,23 @@
+use embassy_executor::Executor;
+
+use super::TaskScheduler;
+
+/// The embassy task that drives the task scheduler.
+///
+/// Only a single scheduler can be running at any one time, as it owns all of the
+/// peripherals through its resource manager.
+#[embassy_executor::task]
+async fn scheduler_task(mut scheduler: TaskScheduler) {
+    if let Err(error) = scheduler.run_async().await {
+        log::error!("The task scheduler has stopped: {error:?}");
+    }
+}
+
+/// Spawn the scheduler onto a new embassy executor and hand the current thread over to it.
+///
+/// Yield requests are resolved using `embassy_time::Timer`, which is driven by the
+/// ESP-IDF timer service through the `embassy` feature of `esp-idf-svc`.
+pub fn start(scheduler: TaskScheduler) -> ! {
+    let executor = Box::leak(Box::new(Executor::new()));
+    executor.run(|spawner| spawner.must_spawn(scheduler_task(scheduler)))
+}
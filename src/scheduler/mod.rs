@@ -1,3 +1,20 @@
+pub mod deadlock;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+mod problem;
 pub mod scheduler;
+mod verifier;
+pub mod waker;
 
+pub use deadlock::{Deadlock, DeadlockResolution, WaitForGraph};
+pub use problem::{Problem, VerifyError};
 pub use scheduler::TaskScheduler;
+pub use verifier::Verifier;
+
+/// The tests of every module in here.
+#[cfg(test)]
+pub const TESTS: &[&[crate::testing::Test]] = &[
+    deadlock::tests::TESTS,
+    scheduler::tests::TESTS,
+    verifier::tests::TESTS,
+];
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use getset::Getters;
+
+/// Represents something wrong with a task that is found before it is scheduled.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Problem {
+    /// The task has no steps to run.
+    NoSteps,
+    /// A step uses a pin that the task has not declared, either directly or
+    /// through the named routine that it calls.
+    UndeclaredPin {
+        step: usize,
+        pin: i32,
+        routine: Option<String>,
+    },
+    /// A step writes to a pin that the task declared as an input, either directly
+    /// or through the named routine that it calls.
+    WriteToInput {
+        step: usize,
+        pin: i32,
+        routine: Option<String>,
+    },
+    /// A pin is not one that the resource manager hands out. Pins declared by
+    /// the task but not used by any step have no step.
+    InvalidPin { step: Option<usize>, pin: i32 },
+    /// A pin is reserved by the chip, such as for the SPI flash, so it can never
+    /// be used. Pins declared by the task but not used by any step have no step.
+    ReservedPin { step: Option<usize>, pin: i32 },
+    /// A step writes to a pin that the chip can only use as an input, either
+    /// directly or through the named routine that it calls.
+    WriteToInputOnlyPin {
+        step: usize,
+        pin: i32,
+        routine: Option<String>,
+    },
+    /// No path through the task ever runs the step.
+    UnreachableStep { step: usize },
+    /// A step returns, but the steps of a task are never run as a routine.
+    ReturnOutsideRoutine { step: usize },
+    /// A pin is declared by the task, but no step uses it. This is only a lint,
+    /// so it doesn't stop the task from being scheduled.
+    UnusedPin { pin: i32 },
+    /// A step drives a strapping pin, either directly or through the named routine
+    /// that it calls, which may stop the chip from booting if it is still driven
+    /// from outside at reset. This is only a lint, as the pin works once booted.
+    DriveStrappingPin {
+        step: usize,
+        pin: i32,
+        routine: Option<String>,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSteps => write!(f, "The task has no steps."),
+            Self::UndeclaredPin {
+                step,
+                pin,
+                routine: None,
+            } => write!(f, "Step {step} uses pin {pin}, which has not been declared."),
+            Self::UndeclaredPin {
+                step,
+                pin,
+                routine: Some(routine),
+            } => write!(
+                f,
+                "Step {step} calls routine {routine}, which uses pin {pin} that has not been declared."
+            ),
+            Self::WriteToInput {
+                step,
+                pin,
+                routine: None,
+            } => write!(
+                f,
+                "Step {step} writes to pin {pin}, which is declared as an input."
+            ),
+            Self::WriteToInput {
+                step,
+                pin,
+                routine: Some(routine),
+            } => write!(
+                f,
+                "Step {step} calls routine {routine}, which writes to pin {pin} that is declared as an input."
+            ),
+            Self::InvalidPin {
+                step: Some(step),
+                pin,
+            } => write!(f, "Step {step} uses pin {pin}, which does not exist."),
+            Self::InvalidPin { step: None, pin } => {
+                write!(f, "Pin {pin} is declared, but it does not exist.")
+            }
+            Self::ReservedPin {
+                step: Some(step),
+                pin,
+            } => write!(f, "Step {step} uses pin {pin}, which is reserved by the chip."),
+            Self::ReservedPin { step: None, pin } => {
+                write!(f, "Pin {pin} is declared, but it is reserved by the chip.")
+            }
+            Self::WriteToInputOnlyPin {
+                step,
+                pin,
+                routine: None,
+            } => write!(
+                f,
+                "Step {step} writes to pin {pin}, which the chip can only use as an input."
+            ),
+            Self::WriteToInputOnlyPin {
+                step,
+                pin,
+                routine: Some(routine),
+            } => write!(
+                f,
+                "Step {step} calls routine {routine}, which writes to pin {pin} that the chip can only use as an input."
+            ),
+            Self::UnreachableStep { step } => write!(f, "Step {step} can never be reached."),
+            Self::ReturnOutsideRoutine { step } => {
+                write!(f, "Step {step} returns, but the task is not a routine.")
+            }
+            Self::UnusedPin { pin } => write!(f, "Pin {pin} is declared, but no step uses it."),
+            Self::DriveStrappingPin {
+                step,
+                pin,
+                routine: None,
+            } => write!(
+                f,
+                "Step {step} drives pin {pin}, which is a strapping pin that decides how the chip boots."
+            ),
+            Self::DriveStrappingPin {
+                step,
+                pin,
+                routine: Some(routine),
+            } => write!(
+                f,
+                "Step {step} calls routine {routine}, which drives pin {pin} that is a strapping pin deciding how the chip boots."
+            ),
+        }
+    }
+}
+
+/// Every problem found with a task that the scheduler refused to accept.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
+pub struct VerifyError {
+    /// The name of the task.
+    #[getset(get = "pub")]
+    task: String,
+    /// The problems found with the task, in the order of the steps they are in.
+    #[getset(get = "pub")]
+    problems: Vec<Problem>,
+}
+
+impl VerifyError {
+    /// Create a new error for the named task.
+    pub fn new(task: impl Into<String>, problems: Vec<Problem>) -> Self {
+        Self {
+            task: task.into(),
+            problems,
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Task '{}' failed verification with {} problem(s):",
+            self.task,
+            self.problems.len()
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VerifyError {}
@@ -1,11 +1,15 @@
-use std::{
-    collections::HashMap,
-    time::{self, Instant},
-};
+use std::{collections::HashMap, task::Poll, time::Instant};
+
+use anyhow::Context;
+use esp_idf_hal::gpio::Level;
 
+use super::{Deadlock, DeadlockResolution, Verifier, WaitForGraph};
 use crate::{
-    resource::{Request, ResourceManager},
-    task::{Shot, Task, TaskStatus},
+    resource::{
+        ChannelResolver, EventResolver, LockResolver, Owner, PinWaitResolver, Request, RequestKind,
+        RequestResolver, ResourceManager, Semaphore, TaskResource, TimerResolver,
+    },
+    task::{RoutineLibrary, Shot, Task, TaskStatus, TaskStep},
 };
 
 pub struct TaskScheduler {
@@ -14,11 +18,31 @@ pub struct TaskScheduler {
     // TODO: Sort by priorities as well. HashMap<TaskStatus, HashMap<TaskPriority, Vec<Task>>
     // tasks[TaskStatus::Ready][TaskPriority::High]
     tasks: HashMap<TaskStatus, Vec<Task>>,
+    /// The resolvers responsible for completing each kind of blocking request.
+    resolvers: HashMap<RequestKind, Box<dyn RequestResolver>>,
+    /// The routines that can be called by any of the scheduled tasks.
+    routines: RoutineLibrary,
+    /// Whether to warn about tasks that are likely to be mistaken when scheduling them.
+    lint: bool,
+    /// What to do when a task that blocks turns out to be deadlocked.
+    deadlock_resolution: DeadlockResolution,
     queue_size: usize,
 }
 
 impl TaskScheduler {
     pub fn new() -> anyhow::Result<Self> {
+        Ok(Self::with_manager(ResourceManager::new()?))
+    }
+
+    /// Create a new scheduler for a test, with a resource manager that doesn't
+    /// take the peripherals.
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self::with_manager(ResourceManager::for_tests())
+    }
+
+    /// Create a new scheduler that hands out the resources of the manager.
+    fn with_manager(manager: ResourceManager<'static>) -> Self {
         let mut tasks = HashMap::new();
 
         // TODO: Clean up.
@@ -29,22 +53,200 @@ impl TaskScheduler {
         tasks.entry(TaskStatus::Exited).or_default();
         tasks.entry(TaskStatus::Suspended).or_default();
 
-        Ok(Self {
-            manager: ResourceManager::new()?,
+        let mut resolvers: HashMap<RequestKind, Box<dyn RequestResolver>> = HashMap::new();
+        resolvers.insert(RequestKind::Yield, Box::new(TimerResolver));
+        resolvers.insert(RequestKind::SleepUntil, Box::new(TimerResolver));
+        resolvers.insert(RequestKind::WaitLevel, Box::new(PinWaitResolver));
+        resolvers.insert(RequestKind::WaitEdge, Box::new(PinWaitResolver));
+        resolvers.insert(RequestKind::Send, Box::new(ChannelResolver));
+        resolvers.insert(RequestKind::Recv, Box::new(ChannelResolver));
+        resolvers.insert(RequestKind::Acquire, Box::new(LockResolver));
+        resolvers.insert(RequestKind::WaitBits, Box::new(EventResolver::default()));
+
+        Self {
+            manager,
             tasks,
+            resolvers,
+            routines: RoutineLibrary::new(),
+            lint: false,
+            deadlock_resolution: DeadlockResolution::default(),
             queue_size: 10,
-        })
+        }
+    }
+
+    /// Register the resolver responsible for completing requests of the given kind.
+    ///
+    /// This replaces any resolver that was previously registered for that kind.
+    pub fn register_resolver(
+        &mut self,
+        kind: RequestKind,
+        resolver: impl RequestResolver + 'static,
+    ) {
+        self.resolvers.insert(kind, Box::new(resolver));
+    }
+
+    /// Turn on or off warnings for tasks that are allowed, but likely mistaken, such
+    /// as tasks that declare pins they never use.
+    pub fn set_lint(&mut self, lint: bool) {
+        self.lint = lint;
+    }
+
+    /// Choose what happens when a task blocks on a lock in a way that can never be
+    /// resolved. By default the deadlock is only reported.
+    pub fn set_deadlock_resolution(&mut self, resolution: DeadlockResolution) {
+        self.deadlock_resolution = resolution;
+    }
+
+    /// Turn on or off deadlock avoidance using the Banker's algorithm.
+    ///
+    /// Tasks must then declare the most permits of each semaphore that they will
+    /// hold at once, and a task is only given a permit if every task could still
+    /// be given the rest of its claim in some order. Otherwise it stays blocked.
+    pub fn set_deadlock_avoidance(&mut self, avoidance: bool) {
+        self.manager.set_avoidance(avoidance);
+    }
+
+    /// Whether every task could still be given the rest of its maximum claim, in
+    /// the sense of the Banker's algorithm.
+    pub fn is_safe(&self) -> bool {
+        self.manager.is_safe()
+    }
+
+    /// Find any tasks that are deadlocked waiting on each other's locks.
+    pub fn deadlock(&self) -> Option<Deadlock> {
+        WaitForGraph::new(&self.tasks[&TaskStatus::Blocked], &self.manager).find_any()
     }
 
-    pub fn schedule(&mut self, task: Task) {
+    /// Accept a task to be run, once it has been checked for mistakes.
+    ///
+    /// The task is refused with a [`VerifyError`](super::VerifyError) listing every
+    /// problem found, rather than faulting part way through running. In lint mode,
+    /// anything likely to be a mistake is logged as a warning.
+    pub fn schedule(&mut self, task: Task) -> anyhow::Result<()> {
+        let verifier = Verifier::new(&self.manager, &self.routines);
+        verifier.check(&task)?;
+
+        if self.lint {
+            for problem in verifier.lint(&task) {
+                log::warn!("Task '{}': {problem}", task.context().name());
+            }
+        }
+
+        self.manager.claim(task.context())?;
+
         self.tasks
             .entry(*task.context().state())
             .or_default()
             .push(task);
+        Ok(())
+    }
+
+    pub fn schedule_bulk(&mut self, tasks: Vec<Task>) -> anyhow::Result<()> {
+        tasks.into_iter().try_for_each(|task| self.schedule(task))
     }
 
-    pub fn schedule_bulk(&mut self, tasks: Vec<Task>) {
-        tasks.into_iter().for_each(|task| self.schedule(task));
+    /// Load a whole workload of tasks and resources from JSON, such as an embedded
+    /// config file, and schedule it.
+    #[cfg(feature = "serde")]
+    pub fn load_json(&mut self, source: &str) -> anyhow::Result<()> {
+        crate::config::Workload::from_json(source)?.apply(self)
+    }
+
+    /// Load a whole workload of tasks and resources from TOML, such as an embedded
+    /// config file, and schedule it.
+    #[cfg(feature = "serde")]
+    pub fn load_toml(&mut self, source: &str) -> anyhow::Result<()> {
+        crate::config::Workload::from_toml(source)?.apply(self)
+    }
+
+    /// Register a named routine that any task can run with a 'Call' step.
+    ///
+    /// Routines share the registers of the task calling them, which is how
+    /// parameters such as pin numbers or durations are passed in.
+    pub fn register_routine(
+        &mut self,
+        name: impl Into<String>,
+        steps: Vec<TaskStep>,
+    ) -> anyhow::Result<()> {
+        self.routines.register(name.into(), steps)
+    }
+
+    /// Register a named channel that tasks can send values to and receive values from.
+    ///
+    /// A channel holds at most `capacity` values, beyond which senders are blocked.
+    pub fn create_channel(
+        &mut self,
+        name: impl Into<String>,
+        capacity: usize,
+    ) -> anyhow::Result<()> {
+        self.manager.create_channel(name.into(), capacity)
+    }
+
+    /// Register a named counting semaphore with the given number of permits.
+    pub fn create_semaphore(
+        &mut self,
+        name: impl Into<String>,
+        permits: usize,
+    ) -> anyhow::Result<()> {
+        self.manager
+            .create_semaphore(name.into(), Semaphore::new(permits))
+    }
+
+    /// Register a named mutex, which can only be held by a single task at a time.
+    pub fn create_mutex(&mut self, name: impl Into<String>) -> anyhow::Result<()> {
+        self.manager
+            .create_semaphore(name.into(), Semaphore::mutex())
+    }
+
+    /// Register a named event group, which starts with no bits set.
+    pub fn create_event_group(&mut self, name: impl Into<String>) -> anyhow::Result<()> {
+        self.manager.create_event_group(name.into())
+    }
+
+    /// Set the bits in the mask on the named event group from outside of any task.
+    ///
+    /// Returns the bits that are set on the group afterwards.
+    pub fn set_bits(&mut self, name: &str, mask: u32) -> anyhow::Result<u32> {
+        self.manager.set_bits(name, mask)
+    }
+
+    /// Clear the bits in the mask on the named event group from outside of any task.
+    ///
+    /// Returns the bits that are still set on the group afterwards.
+    pub fn clear_bits(&mut self, name: &str, mask: u32) -> anyhow::Result<u32> {
+        self.manager.clear_bits(name, mask)
+    }
+
+    /// The tasks that currently own a resource, such as which task owns GPIO 4.
+    ///
+    /// Tasks own every resource they declare from when they are scheduled until
+    /// they exit.
+    pub fn owners_of(&self, resource: TaskResource) -> &[Owner] {
+        self.manager.owners(resource)
+    }
+
+    /// Simulate a GPIO pin changing to the given level.
+    ///
+    /// Tasks waiting on that pin are woken just as they would be by its interrupt,
+    /// which allows tasks that wait on pins to be tested without real hardware.
+    pub fn simulate_pin_change(&mut self, number: i32, level: Level) -> anyhow::Result<()> {
+        self.manager.simulate_pin_change(number, level)
+    }
+
+    /// Start running the scheduler on the current thread.
+    ///
+    /// Without the `embassy` feature this is equivalent to calling `run`.
+    #[cfg(not(feature = "embassy"))]
+    pub fn start(mut self) -> anyhow::Result<()> {
+        self.run()
+    }
+
+    /// Start running the scheduler as a task on an embassy executor.
+    ///
+    /// The executor takes over the current thread, so this never returns.
+    #[cfg(feature = "embassy")]
+    pub fn start(self) -> anyhow::Result<()> {
+        super::embassy::start(self)
     }
 
     pub fn run(&mut self) -> anyhow::Result<()> {
@@ -57,76 +259,385 @@ impl TaskScheduler {
 
             // What it does is essentially sort by priority, and run each task to completion.
             // In the case of an I/O block, preempt the task, when I/O succeeds, immediately swap.
+            self.resolve_blocked()?;
+            self.tick()?;
+        }
+    }
 
-            let [ready_tasks, new_tasks, blocked_tasks] = self
-                .tasks
-                .get_many_mut([&TaskStatus::Ready, &TaskStatus::New, &TaskStatus::Blocked])
-                .unwrap();
-
-            // Resolve I/O requests.
-            // TODO: Move into resource manager or some kind of resource resolver.
-            for blocked_task in blocked_tasks.iter_mut() {
-                let elapsed = blocked_task.context_mut().last_run_timestamp().elapsed();
-                blocked_task
-                    .context_mut()
-                    .block_requests_mut()
-                    .extract_if(|request| match request {
-                        Request::Yield(ms) => elapsed >= time::Duration::from_millis(*ms as u64),
-                    })
-                    .max(); // Just to consume it
+    /// Run the scheduler as an embassy task.
+    ///
+    /// This behaves like `run`, except that blocked tasks are only rescanned once
+    /// the earliest of their deadlines has passed or the scheduler has been woken,
+    /// and the scheduler sleeps on an embassy timer whenever there is nothing ready
+    /// to run instead of spinning.
+    #[cfg(feature = "embassy")]
+    pub async fn run_async(&mut self) -> anyhow::Result<()> {
+        use super::waker;
+        use embassy_futures::select::select;
+        use embassy_time::{Duration, Timer};
+
+        loop {
+            if waker::take()
+                || self
+                    .next_wakeup()
+                    .is_some_and(|wakeup| wakeup <= Instant::now())
+            {
+                self.resolve_blocked()?;
             }
+            self.tick()?;
 
-            // If you can add newly created tasks to the ready queue, do so.
-            if ready_tasks.len() < self.queue_size {
-                new_tasks.sort_by(|a, b| a.context().priority().cmp(&b.context().priority()));
-                while (ready_tasks.len() < self.queue_size) && (!new_tasks.is_empty()) {
-                    ready_tasks.push(new_tasks.pop().unwrap());
+            if !self.tasks[&TaskStatus::Ready].is_empty() {
+                embassy_futures::yield_now().await;
+                continue;
+            }
+
+            match self.next_wakeup() {
+                Some(wakeup) => {
+                    let remaining = wakeup.saturating_duration_since(Instant::now());
+                    let timer = Timer::after(Duration::from_micros(remaining.as_micros() as u64));
+                    select(timer, waker::wait()).await;
+                }
+                None if self.tasks[&TaskStatus::New].is_empty()
+                    && self.tasks[&TaskStatus::Blocked].is_empty() =>
+                {
+                    log::info!("All tasks have exited, stopping the task scheduler.");
+                    return Ok(());
                 }
+                None => waker::wait().await,
             }
+        }
+    }
+
+    /// The earliest instant at which a blocked task could become free.
+    ///
+    /// Blocked tasks that are already free but are waiting for room in the ready
+    /// queue are treated as being free right now.
+    #[cfg(feature = "embassy")]
+    fn next_wakeup(&self) -> Option<Instant> {
+        self.tasks[&TaskStatus::Blocked]
+            .iter()
+            .filter_map(|blocked_task| {
+                let context = blocked_task.context();
+                if context.block_requests().is_empty() {
+                    return Some(Instant::now());
+                }
 
-            // High priority at the end, ready to pop like a stack.
-            ready_tasks.sort_by(|a, b| a.context().priority().cmp(&b.context().priority()));
-            if !ready_tasks.is_empty() {
-                let mut current_task = ready_tasks.pop().unwrap();
-                *current_task.context_mut().state_mut() = TaskStatus::Running;
+                context
+                    .block_requests()
+                    .iter()
+                    .filter_map(|request| {
+                        self.resolvers
+                            .get(&request.kind())
+                            .and_then(|resolver| resolver.deadline(request, context))
+                    })
+                    .max()
+            })
+            .min()
+    }
 
-                current_task
-                    .context_mut()
-                    .set_last_run_timestamp(Instant::now());
-                current_task.step(&mut self.manager)?;
+    /// Resolve the I/O requests of all blocked tasks that have been completed.
+    ///
+    /// Tasks are resolved in priority order, so that when several tasks are waiting
+    /// on the same resource the highest priority one is woken first.
+    fn resolve_blocked(&mut self) -> anyhow::Result<()> {
+        let blocked_tasks = self.tasks.get_mut(&TaskStatus::Blocked).unwrap();
+        blocked_tasks.sort_by(|a, b| b.context().priority().cmp(&a.context().priority()));
 
-                // If an I/O request has been made, transition and block task.
-                if !current_task.context().block_requests().is_empty() {
-                    *current_task.context_mut().state_mut() = TaskStatus::Blocked;
-                    blocked_tasks.push(current_task);
-                } else if *current_task.context().program_counter() >= current_task.steps().len() {
-                    // If the task has reached the end, check whether it is allowed to run again.
-                    match current_task.shots() {
-                        Shot::Custom(0) => {
-                            *current_task.context_mut().state_mut() = TaskStatus::Exited
-                        }
-                        Shot::Infinity | Shot::Custom(_) => ready_tasks.push(current_task),
-                    }
-                } else {
-                    ready_tasks.push(current_task);
+        for blocked_task in blocked_tasks {
+            Self::resolve(&mut self.resolvers, &mut self.manager, blocked_task)?;
+        }
+
+        self.resolvers
+            .values_mut()
+            .for_each(|resolver| resolver.finish(&mut self.manager));
+        Ok(())
+    }
+
+    /// Poll each of the requests a task is blocked on with the resolver registered
+    /// for its kind, writing back the results of those that have been completed.
+    fn resolve(
+        resolvers: &mut HashMap<RequestKind, Box<dyn RequestResolver>>,
+        manager: &mut ResourceManager<'static>,
+        task: &mut Task,
+    ) -> anyhow::Result<()> {
+        let requests = std::mem::take(task.context_mut().block_requests_mut());
+        let mut pending = Vec::with_capacity(requests.len());
+
+        for request in requests {
+            let resolver = resolvers.get_mut(&request.kind()).context(format!(
+                "No resolver registered for {:?} requests!",
+                request.kind()
+            ))?;
+
+            match resolver.poll(&request, task.context(), manager) {
+                Poll::Ready(result) => {
+                    task.context_mut().write_back(result);
+                    resolver.complete(&request, task.context());
                 }
+                Poll::Pending => pending.push(request),
             }
+        }
 
-            // Check how many blocked tasks are now free to be moved back into ready.
-            let mut free_tasks: Vec<_> = blocked_tasks
-                .extract_if(|blocked_task| blocked_task.context().block_requests().is_empty())
-                .collect();
-
-            // TODO: Move this into an entirely new state, the auxillary queue as in the book.
-            // If you can add blocked tasks that are free to the ready queue, do so.
-            if ready_tasks.len() < self.queue_size {
-                free_tasks.sort_by(|a, b| a.context().priority().cmp(&b.context().priority()));
-                while (ready_tasks.len() < self.queue_size) && (!free_tasks.is_empty()) {
-                    ready_tasks.push(free_tasks.pop().unwrap());
-                }
+        *task.context_mut().block_requests_mut() = pending;
+        Ok(())
+    }
+
+    /// Perform a single round of scheduling.
+    ///
+    /// This admits newly created tasks, steps the highest priority ready task, and
+    /// moves any blocked tasks whose requests have been resolved back into the ready queue.
+    fn tick(&mut self) -> anyhow::Result<()> {
+        let [ready_tasks, new_tasks, blocked_tasks, exited_tasks] = self
+            .tasks
+            .get_many_mut([
+                &TaskStatus::Ready,
+                &TaskStatus::New,
+                &TaskStatus::Blocked,
+                &TaskStatus::Exited,
+            ])
+            .unwrap();
+
+        // If you can add newly created tasks to the ready queue, do so.
+        if ready_tasks.len() < self.queue_size {
+            new_tasks.sort_by(|a, b| a.context().priority().cmp(&b.context().priority()));
+            while (ready_tasks.len() < self.queue_size) && (!new_tasks.is_empty()) {
+                ready_tasks.push(new_tasks.pop().unwrap());
+            }
+        }
+
+        // High priority at the end, ready to pop like a stack.
+        ready_tasks.sort_by(|a, b| a.context().priority().cmp(&b.context().priority()));
+        if !ready_tasks.is_empty() {
+            let mut current_task = ready_tasks.pop().unwrap();
+            *current_task.context_mut().state_mut() = TaskStatus::Running;
+
+            let now = Instant::now();
+            current_task.context_mut().set_last_run_timestamp(now);
+            if current_task.context().started_timestamp().is_none() {
+                current_task.context_mut().set_started_timestamp(Some(now));
+            }
+            let stepped = current_task.step(&mut self.manager, &self.routines);
+
+            // A task that yields without a delay is never blocked, it just goes behind
+            // every other ready task of the same priority.
+            let yielded = current_task
+                .context_mut()
+                .block_requests_mut()
+                .extract_if(|request| *request == Request::YieldNow)
+                .count()
+                > 0;
+
+            // Requests that can be completed straight away, such as sending to a channel
+            // that has room, should not block the task at all.
+            let stepped = stepped.and_then(|_| {
+                Self::resolve(&mut self.resolvers, &mut self.manager, &mut current_task)
+            });
+            self.resolvers
+                .values_mut()
+                .for_each(|resolver| resolver.finish(&mut self.manager));
+
+            let finished = current_task.at_end() && *current_task.shots() == Shot::Custom(0);
+
+            if let Err(error) = stepped {
+                // A task that fails is exited rather than bringing down the whole scheduler.
+                log::error!(
+                    "Task '{}' has faulted: {error:?}",
+                    current_task.context().name()
+                );
+                Self::exit(&mut self.manager, exited_tasks, current_task);
+            } else if !current_task.context().block_requests().is_empty() {
+                // If an I/O request has been made, transition and block task.
+                *current_task.context_mut().state_mut() = TaskStatus::Blocked;
+                let id = *current_task.context().id();
+                blocked_tasks.push(current_task);
+                Self::detect_deadlock(
+                    self.deadlock_resolution,
+                    &mut self.manager,
+                    blocked_tasks,
+                    exited_tasks,
+                    id,
+                );
+            } else if finished {
+                // If the task has reached the end, it is not allowed to run again.
+                Self::exit(&mut self.manager, exited_tasks, current_task);
+            } else if yielded {
+                ready_tasks.insert(0, current_task);
             } else {
-                blocked_tasks.extend(free_tasks);
+                ready_tasks.push(current_task);
             }
         }
+
+        // Check how many blocked tasks are now free to be moved back into ready.
+        let mut free_tasks: Vec<_> = blocked_tasks
+            .extract_if(|blocked_task| blocked_task.context().block_requests().is_empty())
+            .collect();
+
+        // TODO: Move this into an entirely new state, the auxillary queue as in the book.
+        // If you can add blocked tasks that are free to the ready queue, do so.
+        if ready_tasks.len() < self.queue_size {
+            free_tasks.sort_by(|a, b| a.context().priority().cmp(&b.context().priority()));
+            while (ready_tasks.len() < self.queue_size) && (!free_tasks.is_empty()) {
+                ready_tasks.push(free_tasks.pop().unwrap());
+            }
+        }
+        blocked_tasks.extend(free_tasks);
+
+        Ok(())
+    }
+
+    /// Check whether the task that just blocked is deadlocked, and resolve it if so.
+    ///
+    /// Faulting a task releases every lock it holds, so the next round of resolving
+    /// blocked tasks hands those locks on to the rest of the cycle.
+    fn detect_deadlock(
+        resolution: DeadlockResolution,
+        manager: &mut ResourceManager<'static>,
+        blocked_tasks: &mut Vec<Task>,
+        exited_tasks: &mut Vec<Task>,
+        id: uuid::Uuid,
+    ) {
+        let Some(deadlock) = WaitForGraph::new(&*blocked_tasks, manager).find(id) else {
+            return;
+        };
+        log::error!("{deadlock}");
+
+        if resolution == DeadlockResolution::FaultLowestPriority {
+            let victim = *deadlock.lowest_priority().id();
+            if let Some(index) = blocked_tasks
+                .iter()
+                .position(|task| *task.context().id() == victim)
+            {
+                let task = blocked_tasks.remove(index);
+                log::error!(
+                    "Task '{}' has faulted to break the deadlock.",
+                    task.context().name()
+                );
+                Self::exit(manager, exited_tasks, task);
+            }
+        }
+    }
+
+    /// Move a task into the exited state.
+    ///
+    /// Any semaphores or mutexes that the task still holds are released, so that
+    /// tasks waiting on them are not blocked forever.
+    fn exit(manager: &mut ResourceManager<'static>, exited_tasks: &mut Vec<Task>, mut task: Task) {
+        for name in manager.release_all(*task.context().id()) {
+            log::warn!(
+                "Task '{}' exited while holding '{name}', releasing it.",
+                task.context().name()
+            );
+        }
+        manager.disown(*task.context().id());
+
+        *task.context_mut().state_mut() = TaskStatus::Exited;
+        exited_tasks.push(task);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::TaskScheduler;
+    use crate::{
+        resource::{RequestResult, ResourceManager},
+        scheduler::{deadlock::tests::blocked_task, DeadlockResolution},
+        task::{Shot, Task, TaskContext, TaskPriority, TaskStatus, TaskStep},
+        testing,
+    };
+
+    testing::tests!(
+        reports_deadlocks,
+        faults_lowest_priority_task,
+        releases_locks_on_exit,
+    );
+
+    /// Block two tasks on each other's mutex, then check for a deadlock from the
+    /// second one as the scheduler does when it blocks, returning the tasks that
+    /// are still blocked and those that exited.
+    fn deadlock(resolution: DeadlockResolution) -> anyhow::Result<(Vec<Task>, Vec<Task>)> {
+        let mut manager = ResourceManager::for_tests();
+        let mut blocked = vec![
+            blocked_task(&mut manager, "Low", TaskPriority::Low, "m1", "m2")?,
+            blocked_task(&mut manager, "High", TaskPriority::High, "m2", "m1")?,
+        ];
+        let mut exited = vec![];
+
+        let id = *blocked[1].context().id();
+        TaskScheduler::detect_deadlock(resolution, &mut manager, &mut blocked, &mut exited, id);
+        Ok((blocked, exited))
+    }
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        tasks
+            .iter()
+            .map(|task| task.context().name().as_str())
+            .collect()
+    }
+
+    fn reports_deadlocks() -> anyhow::Result<()> {
+        let (blocked, exited) = deadlock(DeadlockResolution::Report)?;
+        assert_eq!(names(&blocked), vec!["Low", "High"]);
+        assert!(blocked
+            .iter()
+            .all(|task| *task.context().state() == TaskStatus::Blocked));
+        assert!(exited.is_empty());
+        Ok(())
+    }
+
+    fn faults_lowest_priority_task() -> anyhow::Result<()> {
+        let (blocked, exited) = deadlock(DeadlockResolution::FaultLowestPriority)?;
+        assert_eq!(names(&blocked), vec!["High"]);
+        assert_eq!(names(&exited), vec!["Low"]);
+        assert_eq!(*exited[0].context().state(), TaskStatus::Exited);
+        Ok(())
+    }
+
+    fn releases_locks_on_exit() -> anyhow::Result<()> {
+        let mut scheduler = TaskScheduler::for_tests();
+        scheduler.create_mutex("bus")?;
+        let task = |name: &str, priority: TaskPriority, steps: Vec<TaskStep>| {
+            Task::builder()
+                .context(TaskContext::builder().name(name).priority(priority).build())
+                .steps(steps)
+                .shots(Shot::Custom(0))
+                .build()
+        };
+        // The holder finishes without ever releasing the mutex.
+        scheduler.schedule(task(
+            "Holder",
+            TaskPriority::High,
+            vec![TaskStep::Acquire("bus".into())],
+        )?)?;
+        scheduler.schedule(task(
+            "Waiter",
+            TaskPriority::Low,
+            vec![
+                TaskStep::Acquire("bus".into()),
+                TaskStep::Release("bus".into()),
+            ],
+        )?)?;
+
+        scheduler.tick()?;
+        assert_eq!(names(&scheduler.tasks[&TaskStatus::Exited]), vec!["Holder"]);
+        let holders = |scheduler: &TaskScheduler| {
+            scheduler
+                .manager
+                .semaphore("bus")
+                .map(|semaphore| semaphore.holders().len())
+        };
+        assert_eq!(holders(&scheduler), Some(0));
+
+        for _ in 0..4 {
+            scheduler.resolve_blocked()?;
+            scheduler.tick()?;
+        }
+        let exited = &scheduler.tasks[&TaskStatus::Exited];
+        assert_eq!(names(exited), vec!["Holder", "Waiter"]);
+        assert_eq!(
+            exited[1].context().last_request_result(),
+            &Some(RequestResult::Done)
+        );
+        assert_eq!(holders(&scheduler), Some(0));
+        Ok(())
     }
 }
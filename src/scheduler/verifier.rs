@@ -0,0 +1,437 @@
+use std::{cmp::Reverse, collections::HashSet};
+
+use super::{Problem, VerifyError};
+use crate::{
+    resource::{Access, ResourceManager},
+    task::{RoutineLibrary, Task, TaskStep},
+};
+
+/// Checks a task for mistakes before the scheduler accepts it.
+///
+/// Everything checked here would otherwise only be found once the step runs,
+/// faulting the task part way through its work.
+pub struct Verifier<'v, 'a> {
+    manager: &'v ResourceManager<'a>,
+    routines: &'v RoutineLibrary,
+}
+
+impl<'v, 'a> Verifier<'v, 'a> {
+    /// Create a new verifier for the pins of a manager and a library of routines.
+    pub fn new(manager: &'v ResourceManager<'a>, routines: &'v RoutineLibrary) -> Self {
+        Self { manager, routines }
+    }
+
+    /// Check a task, failing with every problem found if there are any.
+    pub fn check(&self, task: &Task) -> Result<(), VerifyError> {
+        let problems = self.verify(task);
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(VerifyError::new(task.context().name(), problems)),
+        }
+    }
+
+    /// Find every problem with a task.
+    ///
+    /// Pins used by routines that the task calls are checked against the pins of
+    /// the task, as long as the routine has already been registered.
+    pub fn verify(&self, task: &Task) -> Vec<Problem> {
+        let steps = task.steps();
+        let declared = task.context().pins_used();
+        let profile = self.manager.profile();
+        let mut problems = vec![];
+
+        if steps.is_empty() {
+            problems.push(Problem::NoSteps);
+        }
+
+        let mut used = HashSet::new();
+        for (index, step) in steps.iter().enumerate() {
+            for (pin, access, routine) in self.pins(step) {
+                used.insert(pin);
+
+                if profile.is_reserved(pin) {
+                    problems.push(Problem::ReservedPin {
+                        step: Some(index),
+                        pin,
+                    });
+                } else if !self.manager.is_valid_pin(pin) {
+                    problems.push(Problem::InvalidPin {
+                        step: Some(index),
+                        pin,
+                    });
+                } else if !declared.contains(&pin) {
+                    problems.push(Problem::UndeclaredPin {
+                        step: index,
+                        pin,
+                        routine,
+                    });
+                } else if !task.context().pin_mode(pin).allows(access) {
+                    problems.push(Problem::WriteToInput {
+                        step: index,
+                        pin,
+                        routine,
+                    });
+                } else if access == Access::Write && profile.is_input_only(pin) {
+                    problems.push(Problem::WriteToInputOnlyPin {
+                        step: index,
+                        pin,
+                        routine,
+                    });
+                }
+            }
+
+            if *step == TaskStep::Return {
+                problems.push(Problem::ReturnOutsideRoutine { step: index });
+            }
+        }
+
+        for pin in declared.iter().filter(|pin| !used.contains(pin)) {
+            if profile.is_reserved(*pin) {
+                problems.push(Problem::ReservedPin {
+                    step: None,
+                    pin: *pin,
+                });
+            } else if !self.manager.is_valid_pin(*pin) {
+                problems.push(Problem::InvalidPin {
+                    step: None,
+                    pin: *pin,
+                });
+            }
+        }
+
+        let reachable = Self::reachable(steps);
+        problems.extend(
+            (0..steps.len())
+                .filter(|index| !reachable.contains(index))
+                .map(|step| Problem::UnreachableStep { step }),
+        );
+
+        problems
+    }
+
+    /// Find anything about a task that is allowed, but is likely a mistake.
+    ///
+    /// Pins that are declared but never used are only reported when every pin
+    /// is known up front, so tasks that take pins from registers are left alone.
+    pub fn lint(&self, task: &Task) -> Vec<Problem> {
+        let profile = self.manager.profile();
+        let mut problems: Vec<_> = task
+            .steps()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, step)| {
+                self.pins(step)
+                    .into_iter()
+                    .filter(|(pin, access, _)| {
+                        *access == Access::Write && profile.is_strapping(*pin)
+                    })
+                    .map(move |(pin, _, routine)| Problem::DriveStrappingPin {
+                        step: index,
+                        pin,
+                        routine,
+                    })
+            })
+            .collect();
+
+        let indirect = task.steps().iter().any(|step| {
+            matches!(
+                step,
+                TaskStep::ReadGPIOIndirect(..) | TaskStep::WriteGPIOIndirect(..)
+            )
+        });
+        if indirect {
+            return problems;
+        }
+
+        let used: HashSet<_> = task
+            .steps()
+            .iter()
+            .flat_map(|step| self.pins(step))
+            .map(|(pin, ..)| pin)
+            .collect();
+
+        problems.extend(
+            task.context()
+                .pins_used()
+                .iter()
+                .filter(|pin| !used.contains(pin))
+                .map(|pin| Problem::UnusedPin { pin: *pin }),
+        );
+        problems
+    }
+
+    /// The pins that a step uses and how it uses them, along with the routine that
+    /// uses each of them if it is used through a call.
+    fn pins(&self, step: &TaskStep) -> Vec<(i32, Access, Option<String>)> {
+        let mut pins: Vec<_> = Self::pin_access(step)
+            .map(|(pin, access)| (pin, access, None))
+            .into_iter()
+            .collect();
+
+        let TaskStep::Call(routine) = step else {
+            return pins;
+        };
+
+        // Routines can call each other, so follow every call once.
+        let mut pending = vec![routine.clone()];
+        let mut visited = HashSet::new();
+        while let Some(routine) = pending.pop() {
+            if !visited.insert(routine.clone()) {
+                continue;
+            }
+
+            for step in self.routines.get(&routine).unwrap_or_default() {
+                if let Some((pin, access)) = Self::pin_access(step) {
+                    pins.push((pin, access, Some(routine.clone())));
+                }
+                if let TaskStep::Call(next) = step {
+                    pending.push(next.clone());
+                }
+            }
+        }
+
+        // Keep a write to each pin over any reads of it, so that it can be checked.
+        pins.sort_by_key(|(pin, access, _)| (*pin, Reverse(*access)));
+        pins.dedup_by_key(|(pin, ..)| *pin);
+        pins
+    }
+
+    /// The pin that a step uses directly and how it uses it.
+    fn pin_access(step: &TaskStep) -> Option<(i32, Access)> {
+        step.pin().zip(step.access())
+    }
+
+    /// The indices of every step that can be run, starting from the first step.
+    fn reachable(steps: &[TaskStep]) -> HashSet<usize> {
+        let mut reachable = HashSet::new();
+        let mut pending = vec![0];
+
+        while let Some(index) = pending.pop() {
+            let Some(step) = steps.get(index) else {
+                continue;
+            };
+            if reachable.insert(index) {
+                pending.extend(step.successors(index));
+            }
+        }
+
+        reachable
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use esp_idf_hal::gpio::Level;
+
+    use super::Verifier;
+    use crate::{
+        resource::{AccessMode, ResourceManager},
+        scheduler::Problem,
+        task::{Register, RoutineLibrary, Shot, Task, TaskContext, TaskStep},
+        testing,
+    };
+
+    testing::tests!(
+        reports_every_problem,
+        reports_no_steps,
+        reports_undeclared_pins,
+        reports_invalid_pins,
+        reports_unreachable_steps,
+        refuses_reserved_pins,
+        refuses_writes_to_input_only_pins,
+        warns_about_driving_strapping_pins,
+    );
+
+    /// A task that only has the pins declared for it, and none from its steps.
+    fn task(pins: &[(i32, AccessMode)], steps: Vec<TaskStep>) -> anyhow::Result<Task> {
+        Task::builder()
+            .context(
+                TaskContext::builder()
+                    .name("Test")
+                    .pins_used(pins.iter().map(|(pin, _)| *pin).collect())
+                    .pin_modes(pins.iter().copied().collect())
+                    .build(),
+            )
+            .steps(steps)
+            .shots(Shot::Custom(1))
+            .infer_pins(false)
+            .build()
+    }
+
+    fn verify(task: &Task) -> Vec<Problem> {
+        let manager = ResourceManager::for_tests();
+        let routines = RoutineLibrary::new();
+        Verifier::new(&manager, &routines).verify(task)
+    }
+
+    fn lint(task: &Task) -> Vec<Problem> {
+        let manager = ResourceManager::for_tests();
+        let routines = RoutineLibrary::new();
+        Verifier::new(&manager, &routines).lint(task)
+    }
+
+    fn reports_every_problem() -> anyhow::Result<()> {
+        let task = task(
+            &[(5, AccessMode::Input)],
+            vec![
+                TaskStep::WriteGPIO(4, Level::High),
+                TaskStep::WriteGPIO(5, Level::High),
+                TaskStep::ReadGPIO(40, Register::Bool(0)),
+                TaskStep::Jump(5),
+                TaskStep::Return,
+            ],
+        )?;
+        assert_eq!(
+            verify(&task),
+            vec![
+                Problem::UndeclaredPin {
+                    step: 0,
+                    pin: 4,
+                    routine: None
+                },
+                Problem::WriteToInput {
+                    step: 1,
+                    pin: 5,
+                    routine: None
+                },
+                Problem::InvalidPin {
+                    step: Some(2),
+                    pin: 40
+                },
+                Problem::ReturnOutsideRoutine { step: 4 },
+                Problem::UnreachableStep { step: 4 },
+            ]
+        );
+        Ok(())
+    }
+
+    fn reports_no_steps() -> anyhow::Result<()> {
+        assert_eq!(verify(&task(&[], vec![])?), vec![Problem::NoSteps]);
+        Ok(())
+    }
+
+    fn reports_undeclared_pins() -> anyhow::Result<()> {
+        let task = task(
+            &[(4, AccessMode::Output)],
+            vec![
+                TaskStep::WriteGPIO(4, Level::High),
+                TaskStep::ReadGPIO(2, Register::Bool(0)),
+            ],
+        )?;
+        assert_eq!(
+            verify(&task),
+            vec![Problem::UndeclaredPin {
+                step: 1,
+                pin: 2,
+                routine: None
+            }]
+        );
+        Ok(())
+    }
+
+    fn reports_invalid_pins() -> anyhow::Result<()> {
+        // The pin is past the last one of the chip, and the declared pin is never used.
+        let task = task(
+            &[(48, AccessMode::Output), (100, AccessMode::Output)],
+            vec![TaskStep::WriteGPIO(48, Level::High)],
+        )?;
+        assert_eq!(
+            verify(&task),
+            vec![
+                Problem::InvalidPin {
+                    step: Some(0),
+                    pin: 48
+                },
+                Problem::InvalidPin {
+                    step: None,
+                    pin: 100
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    fn reports_unreachable_steps() -> anyhow::Result<()> {
+        let task = task(
+            &[(4, AccessMode::Output)],
+            vec![
+                TaskStep::WriteGPIO(4, Level::High),
+                TaskStep::Jump(0),
+                TaskStep::WriteGPIO(4, Level::Low),
+                TaskStep::YieldNow,
+            ],
+        )?;
+        assert_eq!(
+            verify(&task),
+            vec![
+                Problem::UnreachableStep { step: 2 },
+                Problem::UnreachableStep { step: 3 },
+            ]
+        );
+        Ok(())
+    }
+
+    fn refuses_reserved_pins() -> anyhow::Result<()> {
+        // GPIO 6 to 11 of the ESP32 are wired to the SPI flash.
+        let task = task(
+            &[(6, AccessMode::Output), (7, AccessMode::Output)],
+            vec![TaskStep::WriteGPIO(6, Level::High)],
+        )?;
+        let manager = ResourceManager::for_tests();
+        let routines = RoutineLibrary::new();
+        let error = Verifier::new(&manager, &routines)
+            .check(&task)
+            .expect_err("A task driving a reserved pin should be refused!");
+        assert_eq!(
+            error.problems(),
+            &vec![
+                Problem::ReservedPin {
+                    step: Some(0),
+                    pin: 6
+                },
+                Problem::ReservedPin { step: None, pin: 7 },
+            ]
+        );
+        Ok(())
+    }
+
+    fn refuses_writes_to_input_only_pins() -> anyhow::Result<()> {
+        let task = task(
+            &[(34, AccessMode::Output), (35, AccessMode::Input)],
+            vec![
+                TaskStep::WriteGPIO(34, Level::High),
+                TaskStep::ReadGPIO(35, Register::Bool(0)),
+            ],
+        )?;
+        assert_eq!(
+            verify(&task),
+            vec![Problem::WriteToInputOnlyPin {
+                step: 0,
+                pin: 34,
+                routine: None
+            }]
+        );
+        Ok(())
+    }
+
+    fn warns_about_driving_strapping_pins() -> anyhow::Result<()> {
+        // GPIO 2 and 5 are strapping pins of the ESP32, but only 2 is driven.
+        let task = task(
+            &[(2, AccessMode::Output), (5, AccessMode::Input)],
+            vec![
+                TaskStep::WriteGPIO(2, Level::High),
+                TaskStep::ReadGPIO(5, Register::Bool(0)),
+            ],
+        )?;
+        assert!(verify(&task).is_empty());
+        assert_eq!(
+            lint(&task),
+            vec![Problem::DriveStrappingPin {
+                step: 0,
+                pin: 2,
+                routine: None
+            }]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "embassy")]
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+
+/// Set whenever something outside of the scheduler may have completed a request.
+static WOKEN: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the scheduler when it is sleeping on an embassy executor.
+#[cfg(feature = "embassy")]
+static SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Tell the scheduler that a blocked task may now be free.
+///
+/// This is also called from the GPIO interrupt handler. The signal is guarded
+/// by the critical section of esp-idf-hal, which is safe to take inside of an
+/// interrupt, so the executor is woken straight away.
+pub fn wake() {
+    WOKEN.store(true, Ordering::Release);
+    #[cfg(feature = "embassy")]
+    SIGNAL.signal(());
+}
+
+/// Check whether the scheduler has been woken since this was last called.
+pub fn take() -> bool {
+    WOKEN.swap(false, Ordering::AcqRel)
+}
+
+/// Wait until the scheduler is woken.
+#[cfg(feature = "embassy")]
+pub async fn wait() {
+    SIGNAL.wait().await
+}
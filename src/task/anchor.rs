@@ -0,0 +1,18 @@
+/// Represents the point in time that an absolute sleep is measured from.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TimeAnchor {
+    /// The time at which the current shot of the task started, which is when the
+    /// scheduler first ran the task or when it last went back to its first step.
+    Start,
+    /// The time saved by the last 'MarkTime' step, or the start of the task if
+    /// there is none.
+    ///
+    /// Sleeping relative to the mark moves the mark forward to the deadline, so
+    /// repeatedly sleeping for the same period never drifts.
+    Mark,
+}
@@ -0,0 +1,38 @@
+/// Represents a test made by a conditional jump.
+///
+/// Every condition compares the value of a register against zero, which pairs
+/// with the result of a 'Compare' step, or directly tests a boolean register.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Condition {
+    /// The value is zero, or false.
+    Equal,
+    /// The value is not zero, or true.
+    NotEqual,
+    /// The value is below zero.
+    Less,
+    /// The value is zero or below.
+    LessOrEqual,
+    /// The value is above zero.
+    Greater,
+    /// The value is zero or above.
+    GreaterOrEqual,
+}
+
+impl Condition {
+    /// Check whether the condition holds for the given value.
+    pub fn holds(&self, value: i32) -> bool {
+        match self {
+            Self::Equal => value == 0,
+            Self::NotEqual => value != 0,
+            Self::Less => value < 0,
+            Self::LessOrEqual => value <= 0,
+            Self::Greater => value > 0,
+            Self::GreaterOrEqual => value >= 0,
+        }
+    }
+}
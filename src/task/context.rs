@@ -1,16 +1,27 @@
-use esp_idf_hal::gpio::Level;
+use anyhow::Context;
 use getset::{Getters, MutGetters, Setters};
-use std::time;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time,
+};
 use typed_builder::TypedBuilder;
 
-use super::{TaskPriority, TaskStatus};
-use crate::resource::Request;
+use super::{Frame, Register, RegisterFile, TaskPriority, TaskStatus, Value};
+use crate::resource::{AccessMode, PinConfig, Request, RequestResult};
 
 /// Represents the additional information or context required for scheduling.
 ///
 /// This structure has everything needed to properly schedule tasks to avoid
 /// conflicts and maximise usage of processor cores and time.
+///
+/// When serialized, only the fields that describe the task are kept. The rest
+/// are runtime state, which starts out fresh when the task is loaded again.
 #[derive(Debug, PartialEq, Eq, Clone, Getters, Setters, MutGetters, TypedBuilder)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct TaskContext {
     /// The friendly display name of the task.
     #[getset(get = "pub")]
@@ -30,11 +41,13 @@ pub struct TaskContext {
     /// The current state of the task.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
     #[builder(default=TaskStatus::New)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     state: TaskStatus,
 
     /// The current step that the task is on.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
     #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     program_counter: usize,
 
     /// A list of which pins as resources are assigned to this task.
@@ -42,23 +55,101 @@ pub struct TaskContext {
     #[builder(default)]
     pins_used: Vec<i32>,
 
+    /// How each of the assigned pins is held, where pins that are not listed are
+    /// held exclusively.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "crate::config::pin_table"))]
+    pin_modes: BTreeMap<i32, AccessMode>,
+
+    /// How each of the assigned pins is set up when it is acquired, where pins that
+    /// are not listed are left as the chip resets them.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "crate::config::pin_table"))]
+    pin_configs: BTreeMap<i32, PinConfig>,
+
+    /// The most permits of each semaphore, by name, that the task may hold at once.
+    /// These are only needed when the scheduler avoids deadlock.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    max_claims: BTreeMap<String, usize>,
+
     /// A list of I/O requests to be processed before the task can run.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
     #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
     block_requests: Vec<Request>,
 
     /// The timestamp at which this task was last stepped.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
     #[builder(default=time::Instant::now())]
+    #[cfg_attr(feature = "serde", serde(skip))]
     last_run_timestamp: time::Instant,
 
+    /// The timestamp at which this task was first stepped.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    started_timestamp: Option<time::Instant>,
+
+    /// The timestamp saved by the last 'MarkTime' step, used by 'SleepUntil'.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    mark_timestamp: Option<time::Instant>,
+
+    /// How late the task was woken after its last sleep or yield.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_yield_lateness: time::Duration,
+
+    /// The latest that the task has ever been woken after a sleep or yield.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max_yield_lateness: time::Duration,
+
+    /// The result of the last request made by this task to be completed.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    last_request_result: Option<RequestResult>,
+
+    /// The general purpose registers of the task.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    registers: RegisterFile,
+
+    /// The named variables of the task, used to hold data that doesn't fit in registers.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    variables: HashMap<String, Value>,
+
+    /// The register that the result of the pending request will be written to.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    result_register: Option<Register>,
+
+    /// The routine that is currently running, or none if the task's own steps are running.
+    #[getset(get = "pub", set = "pub", get_mut = "pub")]
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    routine: Option<String>,
+
+    /// The frames to return to once the routines that are currently running finish.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
-    #[builder(default=Level::Low)]
-    last_pin_read_level_register: Level, // TODO: Add support for storing:
-                                         // - Context Data (Data present in registers, used for saving state when restarting a process from middle)
-                                         // - IO Status Info (List of IO requests, devices assigned to it, list of files used, etc.)
-                                         // - Accounting Info (Processor time used so far, clock time used, time limits before its aborted, etc.)
-                                         // TODO: Consider how to manage resources carefully so that there is not dead time.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    call_stack: Vec<Frame>,
+    // TODO: Add support for storing:
+    // - IO Status Info (List of IO requests, devices assigned to it, list of files used, etc.)
+    // - Accounting Info (Processor time used so far, clock time used, time limits before its aborted, etc.)
+    // TODO: Consider how to manage resources carefully so that there is not dead time.
 }
 
 impl Default for TaskContext {
@@ -70,14 +161,29 @@ impl Default for TaskContext {
             state: TaskStatus::New,
             program_counter: 0,
             pins_used: vec![],
+            pin_modes: BTreeMap::new(),
+            pin_configs: BTreeMap::new(),
+            max_claims: BTreeMap::new(),
             block_requests: vec![],
-            last_pin_read_level_register: Level::Low,
+            last_request_result: None,
+            registers: RegisterFile::default(),
+            variables: HashMap::new(),
+            result_register: None,
+            routine: None,
+            call_stack: vec![],
             last_run_timestamp: time::Instant::now(),
+            started_timestamp: None,
+            mark_timestamp: None,
+            last_yield_lateness: time::Duration::ZERO,
+            max_yield_lateness: time::Duration::ZERO,
         }
     }
 }
 
 impl TaskContext {
+    /// The deepest that routines can be nested inside one another.
+    pub const MAX_CALL_DEPTH: usize = 8;
+
     /// Create a new context with the name of the task and its priority.
     ///
     /// All tasks created this way are set the the 'New' state. By default
@@ -89,4 +195,69 @@ impl TaskContext {
             ..Default::default()
         }
     }
+
+    /// How the given pin is held by this task.
+    pub fn pin_mode(&self, pin: i32) -> AccessMode {
+        self.pin_modes.get(&pin).copied().unwrap_or_default()
+    }
+
+    /// How a pin is set up when it is acquired.
+    pub fn pin_config(&self, pin: i32) -> PinConfig {
+        self.pin_configs.get(&pin).copied().unwrap_or_default()
+    }
+
+    /// Write the result of a completed request back into this context.
+    ///
+    /// Results that carry a value are also stored in the register chosen by the
+    /// step that made the request, if there is one.
+    pub fn write_back(&mut self, result: RequestResult) {
+        let value = match result {
+            RequestResult::Level(level) => Some(Value::from(level)),
+            RequestResult::Value(value) => Some(Value::Int(value)),
+            RequestResult::Bits(bits) => Some(Value::Int(bits as i32)),
+            _ => None,
+        };
+
+        if let (Some(register), Some(value)) = (self.result_register.take(), value) {
+            // The register is checked when the request is made, so this cannot fail.
+            let _ = self.registers.set(register, value);
+        }
+
+        if let RequestResult::Slept(lateness) = result {
+            self.last_yield_lateness = lateness;
+            self.max_yield_lateness = self.max_yield_lateness.max(lateness);
+        }
+
+        self.last_request_result = Some(result);
+    }
+
+    /// Enter the named routine, saving where to continue once it returns.
+    ///
+    /// Fails if the call stack is already full, which usually means that a
+    /// routine is calling itself without end.
+    pub fn call(&mut self, routine: String) -> anyhow::Result<()> {
+        (self.call_stack.len() < Self::MAX_CALL_DEPTH)
+            .then_some(())
+            .context(format!(
+                "Calling routine {routine} would nest more than {} routines!",
+                Self::MAX_CALL_DEPTH
+            ))?;
+
+        let frame = Frame::new(self.routine.replace(routine), self.program_counter);
+        self.call_stack.push(frame);
+        self.program_counter = 0;
+        Ok(())
+    }
+
+    /// Leave the current routine, continuing from where it was called.
+    pub fn return_from_routine(&mut self) -> anyhow::Result<()> {
+        let frame = self
+            .call_stack
+            .pop()
+            .context("Cannot return as no routine is running!")?;
+
+        self.routine = frame.routine().clone();
+        self.program_counter = *frame.return_address();
+        Ok(())
+    }
 }
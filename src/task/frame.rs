@@ -0,0 +1,23 @@
+use getset::Getters;
+
+/// Represents a saved position in a program, pushed onto the call stack of a
+/// task by a 'Call' step and popped off again by a 'Return' step.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Getters)]
+pub struct Frame {
+    /// The routine that was running, or none if it was the task's own steps.
+    #[getset(get = "pub")]
+    routine: Option<String>,
+    /// The step to continue from once the call returns.
+    #[getset(get = "pub")]
+    return_address: usize,
+}
+
+impl Frame {
+    /// Create a new frame to return to the given step of a routine or task.
+    pub fn new(routine: Option<String>, return_address: usize) -> Self {
+        Self {
+            routine,
+            return_address,
+        }
+    }
+}
@@ -1,13 +1,37 @@
+mod anchor;
+mod condition;
 mod context;
+mod frame;
+mod operand;
 mod priority;
+mod register;
+mod routine;
 mod shot;
 mod status;
 mod step;
 mod task;
 
+pub use anchor::TimeAnchor;
+pub use condition::Condition;
 pub use context::TaskContext;
+pub use frame::Frame;
+pub use operand::Operand;
 pub use priority::TaskPriority;
+pub use register::{Register, RegisterFile, Value};
+pub use routine::RoutineLibrary;
 pub use shot::Shot;
 pub use status::TaskStatus;
 pub use step::TaskStep;
 pub use task::Task;
+
+/// Helpers for the tests of other modules that build tasks.
+#[cfg(test)]
+pub use task::tests;
+
+/// The tests of every module in here.
+#[cfg(test)]
+pub const TESTS: &[&[crate::testing::Test]] = &[
+    register::tests::TESTS,
+    routine::tests::TESTS,
+    task::tests::TESTS,
+];
@@ -0,0 +1,43 @@
+use super::{Register, RegisterFile, Value};
+
+/// Represents an input to a step, which is either a register or a constant.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Operand {
+    /// The value currently held in a register.
+    Register(Register),
+    /// A constant value.
+    Immediate(Value),
+}
+
+impl Operand {
+    /// Read the value of the operand from the given registers.
+    pub fn value(&self, registers: &RegisterFile) -> anyhow::Result<Value> {
+        match self {
+            Self::Register(register) => registers.get(*register),
+            Self::Immediate(value) => Ok(*value),
+        }
+    }
+}
+
+impl From<Register> for Operand {
+    fn from(register: Register) -> Self {
+        Self::Register(register)
+    }
+}
+
+impl From<i32> for Operand {
+    fn from(value: i32) -> Self {
+        Self::Immediate(Value::Int(value))
+    }
+}
+
+impl From<bool> for Operand {
+    fn from(value: bool) -> Self {
+        Self::Immediate(Value::Bool(value))
+    }
+}
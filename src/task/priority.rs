@@ -4,6 +4,11 @@
 /// The numbers assigned to the default priorities are such that there will
 /// always be a priority between each default as well as above and below it.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[repr(u8)]
 pub enum TaskPriority {
     Low = 64,
@@ -0,0 +1,169 @@
+use anyhow::Context;
+use esp_idf_hal::gpio::Level;
+
+/// Represents one of the registers in the register file of a task.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Register {
+    /// An integer register, written as r0 to r7.
+    Int(u8),
+    /// A boolean register, written as b0 to b7.
+    Bool(u8),
+}
+
+/// Represents a value that can be held in a register or a named variable.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Value {
+    Int(i32),
+    Bool(bool),
+}
+
+impl Value {
+    /// The value as an integer, where true is one and false is zero.
+    pub fn as_int(self) -> i32 {
+        match self {
+            Self::Int(value) => value,
+            Self::Bool(value) => value as i32,
+        }
+    }
+
+    /// The value as a boolean, where any non-zero integer is true.
+    pub fn as_bool(self) -> bool {
+        match self {
+            Self::Int(value) => value != 0,
+            Self::Bool(value) => value,
+        }
+    }
+}
+
+impl From<Level> for Value {
+    fn from(level: Level) -> Self {
+        Self::Bool(level.into())
+    }
+}
+
+/// The general purpose registers of a task.
+///
+/// These are stored in the context of the task, so that saving and restoring the
+/// context is enough to resume a task from the middle of its program.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct RegisterFile {
+    ints: [i32; RegisterFile::INTS],
+    bools: [bool; RegisterFile::BOOLS],
+}
+
+impl RegisterFile {
+    /// The number of integer registers.
+    pub const INTS: usize = 8;
+    /// The number of boolean registers.
+    pub const BOOLS: usize = 8;
+
+    /// Read the value held in a register.
+    pub fn get(&self, register: Register) -> anyhow::Result<Value> {
+        match register {
+            Register::Int(index) => self
+                .ints
+                .get(index as usize)
+                .map(|value| Value::Int(*value)),
+            Register::Bool(index) => self
+                .bools
+                .get(index as usize)
+                .map(|value| Value::Bool(*value)),
+        }
+        .context(format!("Register {register:?} does not exist!"))
+    }
+
+    /// Write a value into a register, converting it to the type of the register.
+    pub fn set(&mut self, register: Register, value: Value) -> anyhow::Result<()> {
+        match register {
+            Register::Int(index) => self
+                .ints
+                .get_mut(index as usize)
+                .map(|slot| *slot = value.as_int()),
+            Register::Bool(index) => self
+                .bools
+                .get_mut(index as usize)
+                .map(|slot| *slot = value.as_bool()),
+        }
+        .context(format!("Register {register:?} does not exist!"))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Register, RegisterFile, Value};
+    use crate::{
+        resource::ResourceManager,
+        task::{Operand, TaskContext, TaskStep},
+        testing,
+    };
+
+    testing::tests!(
+        converts_between_types,
+        rejects_missing_registers,
+        runs_register_steps
+    );
+
+    fn converts_between_types() -> anyhow::Result<()> {
+        let mut registers = RegisterFile::default();
+        registers.set(Register::Int(0), Value::Bool(true))?;
+        registers.set(Register::Bool(0), Value::Int(-5))?;
+        registers.set(Register::Bool(1), Value::Int(0))?;
+
+        assert_eq!(registers.get(Register::Int(0))?, Value::Int(1));
+        assert_eq!(registers.get(Register::Bool(0))?, Value::Bool(true));
+        assert_eq!(registers.get(Register::Bool(1))?, Value::Bool(false));
+        Ok(())
+    }
+
+    fn rejects_missing_registers() -> anyhow::Result<()> {
+        let mut registers = RegisterFile::default();
+        let last = RegisterFile::INTS as u8 - 1;
+        registers.set(Register::Int(last), Value::Int(3))?;
+        assert!(registers.get(Register::Int(last + 1)).is_err());
+        assert!(registers
+            .set(Register::Bool(RegisterFile::BOOLS as u8), Value::Bool(true))
+            .is_err());
+        Ok(())
+    }
+
+    fn runs_register_steps() -> anyhow::Result<()> {
+        let mut manager = ResourceManager::for_tests();
+        let mut context = TaskContext::builder().name("Test").build();
+        let int = |value| Operand::Immediate(Value::Int(value));
+        let steps = [
+            TaskStep::Set(Register::Int(0), int(5)),
+            TaskStep::Add(Register::Int(0), Operand::Register(Register::Int(0))),
+            TaskStep::Sub(Register::Int(0), int(12)),
+            TaskStep::Compare(Register::Int(1), Register::Int(0), int(0)),
+            TaskStep::Store("saved".into(), Register::Int(0)),
+            TaskStep::Set(Register::Int(0), int(0)),
+            TaskStep::Load(Register::Int(2), "saved".into()),
+            TaskStep::Set(Register::Int(3), int(i32::MAX)),
+            TaskStep::Add(Register::Int(3), int(1)),
+        ];
+        for step in steps {
+            step.execute(&mut context, &mut manager)?;
+        }
+
+        let registers = context.registers();
+        assert_eq!(registers.get(Register::Int(0))?, Value::Int(0));
+        assert_eq!(registers.get(Register::Int(1))?, Value::Int(-1));
+        assert_eq!(registers.get(Register::Int(2))?, Value::Int(-2));
+        // Arithmetic wraps around rather than faulting the task.
+        assert_eq!(registers.get(Register::Int(3))?, Value::Int(i32::MIN));
+
+        let missing = TaskStep::Load(Register::Int(0), "missing".into());
+        assert!(missing.execute(&mut context, &mut manager).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use super::TaskStep;
+
+/// A library of named routines that can be called by any task.
+///
+/// Routines take their parameters, such as a pin number or a duration, from the
+/// registers of the task calling them, so one routine can serve many tasks.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct RoutineLibrary {
+    routines: HashMap<String, Vec<TaskStep>>,
+}
+
+impl RoutineLibrary {
+    /// Create a new empty routine library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a routine to the library under the given name.
+    ///
+    /// The jumps in a routine are relative to its own steps, and are checked to
+    /// be in bounds just as they are for a task.
+    pub fn register(&mut self, name: String, steps: Vec<TaskStep>) -> anyhow::Result<()> {
+        (!self.routines.contains_key(&name))
+            .then_some(())
+            .context(format!("Routine {name} has already been registered!"))?;
+        TaskStep::check_jumps(&steps).context(format!("Routine {name} is not valid!"))?;
+
+        self.routines.insert(name, steps);
+        Ok(())
+    }
+
+    /// Get the steps of the routine with the given name, if it has been registered.
+    pub fn get(&self, name: &str) -> Option<&[TaskStep]> {
+        self.routines.get(name).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::RoutineLibrary;
+    use crate::{
+        resource::ResourceManager,
+        task::{Operand, Register, Shot, Task, TaskContext, TaskStep, Value},
+        testing,
+    };
+
+    testing::tests!(
+        calls_and_returns,
+        stops_endless_recursion,
+        rejects_invalid_routines
+    );
+
+    fn task(steps: Vec<TaskStep>) -> anyhow::Result<Task> {
+        Task::builder()
+            .context(TaskContext::builder().name("Test").build())
+            .steps(steps)
+            .shots(Shot::Custom(1))
+            .build()
+    }
+
+    fn calls_and_returns() -> anyhow::Result<()> {
+        let r0 = Register::Int(0);
+        let r1 = Register::Int(1);
+        let mut routines = RoutineLibrary::new();
+        routines.register(
+            "double".into(),
+            vec![TaskStep::Add(r0, Operand::Register(r0)), TaskStep::Return],
+        )?;
+        // Reaching the end of a routine returns from it, as does an early return.
+        routines.register(
+            "quadruple".into(),
+            vec![
+                TaskStep::Call("double".into()),
+                TaskStep::Call("double".into()),
+            ],
+        )?;
+        routines.register(
+            "early".into(),
+            vec![
+                TaskStep::Set(r1, Operand::Immediate(Value::Int(1))),
+                TaskStep::Return,
+                TaskStep::Set(r1, Operand::Immediate(Value::Int(2))),
+            ],
+        )?;
+
+        let mut task = task(vec![
+            TaskStep::Set(r0, Operand::Immediate(Value::Int(3))),
+            TaskStep::Call("quadruple".into()),
+            TaskStep::Add(r0, Operand::Immediate(Value::Int(1))),
+            TaskStep::Call("early".into()),
+        ])?;
+        task.run(&mut ResourceManager::for_tests(), &routines)?;
+
+        let context = task.context();
+        assert_eq!(context.registers().get(r0)?, Value::Int(13));
+        assert_eq!(context.registers().get(r1)?, Value::Int(1));
+        assert_eq!(context.routine(), &None);
+        assert!(context.call_stack().is_empty());
+        Ok(())
+    }
+
+    fn stops_endless_recursion() -> anyhow::Result<()> {
+        let mut routines = RoutineLibrary::new();
+        routines.register("forever".into(), vec![TaskStep::Call("forever".into())])?;
+
+        let mut task = task(vec![TaskStep::Call("forever".into())])?;
+        let error = task
+            .run(&mut ResourceManager::for_tests(), &routines)
+            .expect_err("A routine calling itself forever should fail.");
+        assert!(format!("{error:?}").contains("would nest more than"));
+        assert_eq!(
+            task.context().call_stack().len(),
+            TaskContext::MAX_CALL_DEPTH
+        );
+        Ok(())
+    }
+
+    fn rejects_invalid_routines() -> anyhow::Result<()> {
+        let mut routines = RoutineLibrary::new();
+        routines.register("blink".into(), vec![TaskStep::Jump(1)])?;
+        assert!(routines.register("blink".into(), vec![]).is_err());
+        assert!(routines
+            .register("far".into(), vec![TaskStep::Jump(2)])
+            .is_err());
+        assert!(routines.get("far").is_none());
+
+        // Calling a routine that was never registered faults the task.
+        let mut task = task(vec![TaskStep::Call("missing".into())])?;
+        assert!(task
+            .run(&mut ResourceManager::for_tests(), &routines)
+            .is_err());
+        Ok(())
+    }
+}
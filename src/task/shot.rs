@@ -5,6 +5,11 @@ use std::ops::SubAssign;
 /// This can be a fine grained value or the default of Infinity.
 /// Infinity is equivalent to Custom(0).
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Shot {
     Infinity,
     Custom(usize),
@@ -1,68 +1,396 @@
-use std::mem::ManuallyDrop;
+use std::time;
 
-use crate::resource::{Request, ResourceManager, TaskResource};
+use crate::resource::{Access, Edge, Request, ResourceManager, TaskResource, WaitMode};
 
-use super::TaskContext;
+use super::{Condition, Operand, Register, TaskContext, TimeAnchor, Value};
 use anyhow::Context;
-use esp_idf_hal::gpio::{Level, PinDriver};
+use esp_idf_hal::gpio::Level;
 
 /// Represents the smallest instruction that a task can perform.
 ///
 /// A task is made up of a series of steps, which can be one of these
 /// atomic operations, which can include IO operations such as reading,
 /// writing, logging, as well as yield control back to the scheduler.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum TaskStep {
     /// Read the output from a GPIO pin and store it in a register.
-    ReadGPIO(i32),
+    #[cfg_attr(feature = "serde", serde(rename = "read_gpio"))]
+    ReadGPIO(i32, Register),
     /// Write to an input GPIO pin the given level or state.
-    WriteGPIO(i32, Level),
+    #[cfg_attr(feature = "serde", serde(rename = "write_gpio"))]
+    WriteGPIO(
+        i32,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::LevelDef"))] Level,
+    ),
     /// Yield execution back to the scheduler.
     Yield(u32),
+    /// Give up the current slot to other ready tasks of the same priority, without any delay.
+    YieldNow,
+    /// Sleep until the given number of milliseconds after the anchor have passed.
+    SleepUntil(u32, TimeAnchor),
+    /// Save the current time as the mark used by 'SleepUntil'.
+    MarkTime,
+    /// Block until a GPIO pin reaches the given level, with an optional timeout in milliseconds.
+    ///
+    /// The level of the pin is stored in the register, if one is given, once it is
+    /// reached. The register is left as it was if the wait times out.
+    WaitLevel(
+        i32,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::LevelDef"))] Level,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::timeout"))] Option<u32>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::result_register"))]
+        Option<Register>,
+    ),
+    /// Block until a GPIO pin sees the given edge, with an optional timeout in milliseconds.
+    ///
+    /// The level of the pin after the edge is stored in the register, if one is
+    /// given. The register is left as it was if the wait times out.
+    WaitEdge(
+        i32,
+        Edge,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::timeout"))] Option<u32>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::result_register"))]
+        Option<Register>,
+    ),
+    /// Send a value to the named channel, blocking while the channel is full.
+    Send(String, i32),
+    /// Receive a value from the named channel and store it in a register, blocking while it is empty.
+    Recv(String, Register),
+    /// Save the value of a register in the named variable.
+    Store(String, Register),
+    /// Load the value of the named variable into a register.
+    Load(Register, String),
+    /// Write the value of the operand into a register.
+    Set(Register, Operand),
+    /// Add the value of the operand to a register.
+    Add(Register, Operand),
+    /// Subtract the value of the operand from a register.
+    Sub(Register, Operand),
+    /// Compare the second register with the operand, storing -1, 0 or 1 into the first
+    /// register when it is less than, equal to, or greater than the operand.
+    Compare(Register, Register, Operand),
+    /// Continue from the step at the given index.
+    Jump(usize),
+    /// Continue from the step at the given index if the condition holds for the register.
+    JumpIf(Register, Condition, usize),
+    /// Decrement a loop counter register, continuing from the step at the given index
+    /// while it is still above zero.
+    Loop(Register, usize),
+    /// Call the named routine, continuing from the next step once it returns.
+    Call(String),
+    /// Return from the routine that is currently running.
+    Return,
+    /// Read the GPIO pin whose number is held in the first register into the second.
+    #[cfg_attr(feature = "serde", serde(rename = "read_gpio_indirect"))]
+    ReadGPIOIndirect(Register, Register),
+    /// Write the given level to the GPIO pin whose number is held in a register.
+    #[cfg_attr(feature = "serde", serde(rename = "write_gpio_indirect"))]
+    WriteGPIOIndirect(
+        Register,
+        #[cfg_attr(feature = "serde", serde(with = "crate::config::LevelDef"))] Level,
+    ),
+    /// Yield for the number of milliseconds held in a register.
+    YieldIndirect(Register),
+    /// Acquire a permit from the named semaphore or mutex, blocking until one is free.
+    Acquire(String),
+    /// Give back a permit held on the named semaphore or mutex.
+    Release(String),
+    /// Set the bits in the mask on the named event group.
+    SetBits(String, u32),
+    /// Clear the bits in the mask on the named event group.
+    ClearBits(String, u32),
+    /// Block until all or any of the bits in the mask are set on the named event
+    /// group, optionally clearing them once the wait is over.
+    WaitBits(String, u32, WaitMode, bool),
     // TODO: Consider using TaskResources instead of their inner values for ease.
     // TODO: Allow the usage of analog and spi pins.
     // TODO: Add more operations to handle files and logging.
 }
 
 impl<'a> TaskStep {
+    /// The index of the step that this step may jump to, if it is a jump.
+    pub fn jump_target(&self) -> Option<usize> {
+        match self {
+            Self::Jump(target) | Self::JumpIf(.., target) | Self::Loop(_, target) => Some(*target),
+            _ => None,
+        }
+    }
+
+    /// The pin that this step uses directly, if it uses one.
+    ///
+    /// Steps that take their pin from a register are left out, as the pin is only
+    /// known once the task is running.
+    pub fn pin(&self) -> Option<i32> {
+        match self {
+            Self::ReadGPIO(pin, _)
+            | Self::WriteGPIO(pin, _)
+            | Self::WaitLevel(pin, ..)
+            | Self::WaitEdge(pin, ..) => Some(*pin),
+            _ => None,
+        }
+    }
+
+    /// How the step uses the pin that it works with, if it uses one at all.
+    ///
+    /// Unlike `pin`, this includes steps that take their pin from a register.
+    pub fn access(&self) -> Option<Access> {
+        match self {
+            Self::ReadGPIO(..)
+            | Self::ReadGPIOIndirect(..)
+            | Self::WaitLevel(..)
+            | Self::WaitEdge(..) => Some(Access::Read),
+            Self::WriteGPIO(..) | Self::WriteGPIOIndirect(..) => Some(Access::Write),
+            _ => None,
+        }
+    }
+
+    /// The steps that may run straight after this one, which is at the given index.
+    ///
+    /// An index equal to the number of steps means that the shot or routine ends.
+    pub fn successors(&self, index: usize) -> Vec<usize> {
+        match self {
+            Self::Jump(target) => vec![*target],
+            Self::JumpIf(.., target) | Self::Loop(_, target) => vec![index + 1, *target],
+            Self::Return => vec![],
+            _ => vec![index + 1],
+        }
+    }
+
+    /// Check that every jump in a list of steps lands on one of those steps.
+    ///
+    /// Jumping to the index just past the last step is allowed, and ends the
+    /// current shot of a task or returns from a routine.
+    pub fn check_jumps(steps: &[TaskStep]) -> anyhow::Result<()> {
+        for (index, step) in steps.iter().enumerate() {
+            if let Some(target) = step.jump_target() {
+                (target <= steps.len()).then_some(()).context(format!(
+                    "Step {index} jumps to step {target}, but there are only {} steps!",
+                    steps.len()
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Execute a single step of a task with the given context and acquired resources.
     pub fn execute(
-        &mut self,
+        &self,
         context: &mut TaskContext,
         manager: &mut ResourceManager<'a>,
     ) -> anyhow::Result<Option<Request>> {
         match self {
-            Self::ReadGPIO(pin_number) => {
-                let pin = manager.acquire(TaskResource::Pin(*pin_number), context)?;
-                let driver = ManuallyDrop::new(
-                    PinDriver::input(pin.reborrow())
-                        .context("This error is not possible as the driver is only used once before being wiped.")?
-                );
-
-                // TODO: Incorporate registers to store the level.
-                // TODO: Think about the fact that despite the pin being a resource, we aren't waiting for it.
-                // If we had a WriteFile step, we couldn't do this.
-                log::info!(
-                    "Reading GPIO Pin {} Resulted In The Following Output: {:?}",
-                    driver.pin(),
-                    driver.get_level()
-                );
-
-                context.set_last_pin_read_level_register(driver.get_level());
+            Self::ReadGPIO(pin_number, register) => {
+                Self::read_pin(*pin_number, *register, context, manager)?;
             }
             Self::WriteGPIO(pin_number, level) => {
-                let pin = manager.acquire(TaskResource::Pin(*pin_number), context)?;
-                let mut driver = ManuallyDrop::new(
-                    PinDriver::output(pin.reborrow())
-                        .context("This error is not possible as the driver is only used once before being wiped.")?
-                );
-                driver.set_level(*level)?;
+                Self::write_pin(*pin_number, *level, context, manager)?;
+            }
+            Self::ReadGPIOIndirect(pin_register, register) => {
+                let pin_number = context.registers().get(*pin_register)?.as_int();
+                Self::read_pin(pin_number, *register, context, manager)?;
+            }
+            Self::WriteGPIOIndirect(pin_register, level) => {
+                let pin_number = context.registers().get(*pin_register)?.as_int();
+                Self::write_pin(pin_number, *level, context, manager)?;
             }
             Self::Yield(ms) => {
                 return Ok(Some(Request::Yield(*ms as _)));
             }
+            Self::YieldIndirect(register) => {
+                let ms = context.registers().get(*register)?.as_int().max(0);
+                return Ok(Some(Request::Yield(ms as _)));
+            }
+            Self::YieldNow => {
+                return Ok(Some(Request::YieldNow));
+            }
+            Self::SleepUntil(ms, anchor) => {
+                let started = context
+                    .started_timestamp()
+                    .unwrap_or(*context.last_run_timestamp());
+                let offset = time::Duration::from_millis(*ms as u64);
+
+                let deadline = match anchor {
+                    TimeAnchor::Start => started + offset,
+                    TimeAnchor::Mark => {
+                        let deadline = context.mark_timestamp().unwrap_or(started) + offset;
+                        context.set_mark_timestamp(Some(deadline));
+                        deadline
+                    }
+                };
+
+                return Ok(Some(Request::SleepUntil(deadline)));
+            }
+            Self::MarkTime => {
+                context.set_mark_timestamp(Some(*context.last_run_timestamp()));
+            }
+            Self::WaitLevel(pin_number, level, timeout, register) => {
+                manager.watch(TaskResource::Pin(*pin_number), context)?;
+                Self::store_result(*register, context)?;
+                return Ok(Some(Request::WaitLevel(
+                    *pin_number,
+                    *level,
+                    timeout.map(|ms| ms as _),
+                )));
+            }
+            Self::WaitEdge(pin_number, edge, timeout, register) => {
+                let watch = manager.watch(TaskResource::Pin(*pin_number), context)?;
+                Self::store_result(*register, context)?;
+                return Ok(Some(Request::WaitEdge(
+                    *pin_number,
+                    *edge,
+                    timeout.map(|ms| ms as _),
+                    watch.edges(*edge),
+                )));
+            }
+            Self::Send(channel, value) => {
+                manager
+                    .channel(channel)
+                    .context(format!("Channel {channel} has not been registered!"))?;
+                return Ok(Some(Request::Send(channel.clone(), *value)));
+            }
+            Self::Recv(channel, register) => {
+                manager
+                    .channel(channel)
+                    .context(format!("Channel {channel} has not been registered!"))?;
+                Self::store_result(Some(*register), context)?;
+                return Ok(Some(Request::Recv(channel.clone())));
+            }
+            Self::Store(variable, register) => {
+                let value = context.registers().get(*register)?;
+                context.variables_mut().insert(variable.clone(), value);
+            }
+            Self::Load(register, variable) => {
+                let value = *context
+                    .variables()
+                    .get(variable)
+                    .context(format!("Variable {variable} has not been stored!"))?;
+                context.registers_mut().set(*register, value)?;
+            }
+            Self::Set(register, operand) => {
+                let value = operand.value(context.registers())?;
+                context.registers_mut().set(*register, value)?;
+            }
+            Self::Add(register, operand) => {
+                let lhs = context.registers().get(*register)?.as_int();
+                let rhs = operand.value(context.registers())?.as_int();
+                context
+                    .registers_mut()
+                    .set(*register, Value::Int(lhs.wrapping_add(rhs)))?;
+            }
+            Self::Sub(register, operand) => {
+                let lhs = context.registers().get(*register)?.as_int();
+                let rhs = operand.value(context.registers())?.as_int();
+                context
+                    .registers_mut()
+                    .set(*register, Value::Int(lhs.wrapping_sub(rhs)))?;
+            }
+            Self::Compare(result, register, operand) => {
+                let lhs = context.registers().get(*register)?.as_int();
+                let rhs = operand.value(context.registers())?.as_int();
+                context
+                    .registers_mut()
+                    .set(*result, Value::Int(lhs.cmp(&rhs) as i32))?;
+            }
+            Self::Jump(target) => {
+                context.set_program_counter(*target);
+            }
+            Self::JumpIf(register, condition, target) => {
+                if condition.holds(context.registers().get(*register)?.as_int()) {
+                    context.set_program_counter(*target);
+                }
+            }
+            Self::Loop(register, target) => {
+                let remaining = context.registers().get(*register)?.as_int().wrapping_sub(1);
+                context
+                    .registers_mut()
+                    .set(*register, Value::Int(remaining))?;
+                if remaining > 0 {
+                    context.set_program_counter(*target);
+                }
+            }
+            Self::Call(routine) => {
+                context.call(routine.clone())?;
+            }
+            Self::Return => {
+                context.return_from_routine()?;
+            }
+            Self::Acquire(semaphore) => {
+                manager
+                    .semaphore(semaphore)
+                    .context(format!("Semaphore {semaphore} has not been registered!"))?;
+                manager.check_claim(semaphore, context)?;
+                return Ok(Some(Request::Acquire(semaphore.clone())));
+            }
+            Self::Release(semaphore) => {
+                manager.release(semaphore, context)?;
+            }
+            Self::SetBits(group, mask) => {
+                manager.set_bits(group, *mask)?;
+            }
+            Self::ClearBits(group, mask) => {
+                manager.clear_bits(group, *mask)?;
+            }
+            Self::WaitBits(group, mask, mode, clear_on_exit) => {
+                manager
+                    .event_group(group)
+                    .context(format!("Event group {group} has not been registered!"))?;
+                return Ok(Some(Request::WaitBits(
+                    group.clone(),
+                    *mask,
+                    *mode,
+                    *clear_on_exit,
+                )));
+            }
         }
 
         Ok(None)
     }
 }
+
+impl<'a> TaskStep {
+    /// Read the level of a GPIO pin into a register.
+    fn read_pin(
+        pin_number: i32,
+        register: Register,
+        context: &mut TaskContext,
+        manager: &mut ResourceManager<'a>,
+    ) -> anyhow::Result<()> {
+        let level = manager
+            .acquire(TaskResource::Pin(pin_number), Access::Read, context)?
+            .read()?;
+
+        // TODO: Think about the fact that despite the pin being a resource, we aren't waiting for it.
+        // If we had a WriteFile step, we couldn't do this.
+        log::info!("Reading GPIO Pin {pin_number} Resulted In The Following Output: {level:?}");
+
+        context.registers_mut().set(register, level.into())
+    }
+
+    /// Store the result of the request that the step makes in the register, if
+    /// one is given, once the request completes.
+    fn store_result(register: Option<Register>, context: &mut TaskContext) -> anyhow::Result<()> {
+        if let Some(register) = register {
+            context.registers().get(register)?;
+        }
+        context.set_result_register(register);
+        Ok(())
+    }
+
+    /// Write a level to a GPIO pin.
+    fn write_pin(
+        pin_number: i32,
+        level: Level,
+        context: &mut TaskContext,
+        manager: &mut ResourceManager<'a>,
+    ) -> anyhow::Result<()> {
+        manager
+            .acquire(TaskResource::Pin(pin_number), Access::Write, context)?
+            .write(level)
+    }
+}
@@ -1,5 +1,10 @@
-use super::{Shot, TaskContext, TaskPriority, TaskStep};
-use crate::resource::{ResourceManager, TaskResource};
+use std::fmt;
+
+use super::{RoutineLibrary, Shot, TaskContext, TaskPriority, TaskStep};
+use crate::{
+    asm, binary,
+    resource::{Access, AccessMode, PinConfig, ResourceManager, TaskResource},
+};
 
 use anyhow::Context;
 use getset::{Getters, MutGetters, Setters};
@@ -11,11 +16,18 @@ use typed_builder::TypedBuilder;
 /// some context to it, such as which pins or other IO does it hold access to,
 /// a unique id representing it, it's state and priority, etc.
 ///
-/// When a task is created, no resources are assigned to it. Instead, resources
-/// must be manually assigned or acquired by the task on creation. If this is
-/// not done, access to the resource will be denied and the task will immediately
-/// enter an aborted or exited state.
+/// When a task is created, the pins that its steps use directly are assigned to
+/// it. Anything else, such as pins taken from registers or used by routines it
+/// calls, must be assigned by hand. Access to a resource that was not assigned
+/// is denied, and the task will immediately enter an aborted or exited state.
+///
+/// Building a task checks that every jump in its steps lands inside the task,
+/// so the builder returns a result rather than the task itself. Inferring the
+/// pins can be turned off with `.infer_pins(false)` to only use the pins that
+/// are assigned by hand.
 #[derive(Debug, PartialEq, Eq, Clone, Getters, Setters, MutGetters, TypedBuilder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(build_method(into = anyhow::Result<Task>))]
 pub struct Task {
     /// Additional accounting information required for proper task management.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
@@ -26,6 +38,11 @@ pub struct Task {
     /// The number of times the task can be fully run.
     #[getset(get = "pub", set = "pub", get_mut = "pub")]
     shots: Shot,
+    /// Whether the pins used by the steps are declared when the task is built.
+    #[getset(get = "pub")]
+    #[builder(default = true)]
+    #[cfg_attr(feature = "serde", serde(default = "Task::default_infer_pins"))]
+    infer_pins: bool,
     // TODO: Move shots into context, or structure tasks to be more in line with the book.
     // i.e., the three parts of a process.
     // TODO: Allow the use of async functions directly by abusing 'Future'.
@@ -33,35 +50,103 @@ pub struct Task {
 }
 
 impl<'a> Task {
+    /// The most steps that `run` performs before giving up on the task finishing.
+    pub const MAX_RUN_STEPS: usize = 100_000;
+
     /// Create a new task with the given name, priority, and instructions to perform.
     ///
-    /// This task does not have any resources assigned to it and these must be assigned
-    /// manually. Additionally, the task starts out by default in the 'New' state.
+    /// The pins used directly by the steps are assigned to the task, but any other
+    /// resources must be assigned manually. Additionally, the task starts out by
+    /// default in the 'New' state.
     pub fn new(name: &str, priority: TaskPriority, shots: Shot, steps: Vec<TaskStep>) -> Self {
-        Self {
+        let mut task = Self {
             context: TaskContext::new(name.into(), priority),
             steps,
             shots,
+            infer_pins: true,
+        };
+        task.declare_inferred_pins();
+        task
+    }
+
+    /// The pins that the steps of the task use directly, in ascending order.
+    ///
+    /// Pins taken from registers or used by routines that the task calls cannot
+    /// be known from the steps alone, so they are left out.
+    pub fn inferred_pins(&self) -> Vec<i32> {
+        let mut pins: Vec<_> = self.steps.iter().filter_map(TaskStep::pin).collect();
+        pins.sort();
+        pins.dedup();
+        pins
+    }
+
+    /// Declare every pin that the steps use which hasn't already been declared.
+    ///
+    /// Pins that no step writes to are declared as inputs, so that other tasks
+    /// may read them too. This is only done when the task can't write to a pin
+    /// that it doesn't name, through a register or a routine, as those pins
+    /// can't be known until the task runs.
+    pub fn declare_inferred_pins(&mut self) {
+        let writes_unknown_pins = self
+            .steps
+            .iter()
+            .any(|step| matches!(step, TaskStep::WriteGPIOIndirect(..) | TaskStep::Call(..)));
+
+        for pin in self.inferred_pins() {
+            if self.context.pins_used().contains(&pin) {
+                continue;
+            }
+
+            self.context.pins_used_mut().push(pin);
+            let written = self
+                .steps
+                .iter()
+                .any(|step| step.pin() == Some(pin) && step.access() == Some(Access::Write));
+            if !written && !writes_unknown_pins {
+                self.context.pin_modes_mut().insert(pin, AccessMode::Input);
+            }
         }
     }
 
+    #[cfg(feature = "serde")]
+    fn default_infer_pins() -> bool {
+        true
+    }
+
     /// Run a task to completion without yielding for any reason.
     ///
     /// This method is not recommended to be used directly as it bypasses the task
-    /// scheduler entirely, causing no time gains to be had.
+    /// scheduler entirely, causing no time gains to be had. Any requests made by the
+    /// steps are dropped rather than waited on.
+    ///
+    /// As nothing is waited on, a task that jumps back until something changes
+    /// would never finish, so running more than `MAX_RUN_STEPS` steps in one go
+    /// fails instead, leaving the task at the step it got to.
     ///
     /// If a task had some steps performed by the scheduler before being manually
     /// ran, then the task will continue from that point on instead of restarting.
-    pub fn run(&mut self, manager: &mut ResourceManager<'a>) -> anyhow::Result<()> {
-        if *self.context.program_counter() >= self.steps.len() {
-            *self.context.program_counter_mut() = 0;
-            self.shots -= 1;
+    pub fn run(
+        &mut self,
+        manager: &mut ResourceManager<'a>,
+        routines: &RoutineLibrary,
+    ) -> anyhow::Result<()> {
+        if self.at_end() {
+            self.next_shot();
         }
 
-        let steps = &mut self.steps[*self.context.program_counter()..];
-        for step in steps {
-            step.execute(&mut self.context, manager)?;
-            *self.context.program_counter_mut() += 1;
+        let mut steps = 0;
+        while !self.at_end() {
+            (steps < Self::MAX_RUN_STEPS)
+                .then_some(())
+                .context(format!(
+                    "Task '{}' did not finish within {} steps, so it likely loops forever!",
+                    self.context.name(),
+                    Self::MAX_RUN_STEPS
+                ))?;
+
+            self.step(manager, routines)?;
+            self.context.block_requests_mut().clear();
+            steps += 1;
         }
 
         self.shots -= 1;
@@ -74,27 +159,118 @@ impl<'a> Task {
     /// gains in terms of time if there are multiple tasks that continuously yield.
     ///
     /// This method also saves the current step that it is on so that it will always
-    /// start where it left off.
-    pub fn step(&mut self, manager: &mut ResourceManager) -> anyhow::Result<()> {
-        if *self.context.program_counter() >= self.steps.len() {
-            *self.context.program_counter_mut() = 0;
-            self.shots -= 1;
+    /// start where it left off. Reaching the end of a routine returns from it, as if
+    /// the routine had ended with a 'Return' step.
+    pub fn step(
+        &mut self,
+        manager: &mut ResourceManager,
+        routines: &RoutineLibrary,
+    ) -> anyhow::Result<()> {
+        if self.at_end() {
+            self.next_shot();
         }
 
-        let task_step = self
-            .steps
-            .get_mut(*self.context.program_counter())
+        let task_step = Self::current_steps(&self.steps, &self.context, routines)?
+            .get(*self.context.program_counter())
             .context("Program counter set outside bounds.")?;
 
+        if let TaskStep::Call(routine) = task_step {
+            routines
+                .get(routine)
+                .context(format!("Routine {routine} has not been registered!"))?;
+        }
+
         *self.context.program_counter_mut() += 1;
 
         let result = task_step.execute(&mut self.context, manager)?;
         if let Some(request) = result {
-            self.context_mut().block_requests_mut().push(request);
+            self.context.block_requests_mut().push(request);
         }
+
+        while self.context.routine().is_some()
+            && *self.context.program_counter()
+                >= Self::current_steps(&self.steps, &self.context, routines)?.len()
+        {
+            self.context.return_from_routine()?;
+        }
+
         Ok(())
     }
 
+    /// Go back to the first step for another shot, which is when sleeps anchored
+    /// to the start of the task are measured from again.
+    fn next_shot(&mut self) {
+        *self.context.program_counter_mut() = 0;
+        self.shots -= 1;
+        let now = *self.context.last_run_timestamp();
+        self.context.set_started_timestamp(Some(now));
+    }
+
+    /// Whether the task has run every one of its steps in the current shot.
+    pub fn at_end(&self) -> bool {
+        self.context.routine().is_none() && *self.context.program_counter() >= self.steps.len()
+    }
+
+    /// The steps of the routine that is currently running, or the task's own steps.
+    fn current_steps<'s>(
+        steps: &'s [TaskStep],
+        context: &TaskContext,
+        routines: &'s RoutineLibrary,
+    ) -> anyhow::Result<&'s [TaskStep]> {
+        match context.routine() {
+            Some(routine) => routines
+                .get(routine)
+                .context(format!("Routine {routine} has not been registered!")),
+            None => Ok(steps),
+        }
+    }
+
+    /// Check that every jump in the task lands on one of its steps.
+    ///
+    /// Jumping to the index just past the last step is allowed, and ends the
+    /// current shot of the task.
+    pub fn check_jumps(&self) -> anyhow::Result<()> {
+        TaskStep::check_jumps(&self.steps)
+    }
+
+    /// Load a task from a compact binary image, checking that it is intact.
+    pub fn from_bytes(image: &[u8]) -> anyhow::Result<Self> {
+        binary::decode(image)
+    }
+
+    /// Save the task as a compact binary image, for storage or transfer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        binary::encode(self)
+    }
+
+    /// Load a task from JSON, checking that its jumps are in bounds.
+    #[cfg(feature = "serde")]
+    pub fn from_json(source: &str) -> anyhow::Result<Self> {
+        serde_json::from_str::<Self>(source)
+            .context("Could not parse task from JSON!")?
+            .into()
+    }
+
+    /// Save the task as JSON, leaving out any state from running it.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        serde_json::to_string_pretty(self).context("Could not write task as JSON!")
+    }
+
+    /// Load a task from TOML, checking that its jumps are in bounds.
+    #[cfg(feature = "serde")]
+    pub fn from_toml(source: &str) -> anyhow::Result<Self> {
+        toml::from_str::<Self>(source)
+            .context("Could not parse task from TOML!")?
+            .into()
+    }
+
+    /// Save the task as TOML, leaving out any state from running it.
+    #[cfg(feature = "serde")]
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        toml::to_string_pretty(self).context("Could not write task as TOML!")
+    }
+
     /// Assign a resource to be usable by this task.
     ///
     /// This method saves additional context to the task regarding which resource
@@ -105,11 +281,325 @@ impl<'a> Task {
     /// actual resources be used directly.
     ///
     /// Returns the task itself for convenience.
-    pub fn assign(mut self, resource: TaskResource) -> Self {
+    pub fn assign(self, resource: TaskResource) -> Self {
+        self.declare(resource, AccessMode::Output)
+    }
+
+    /// Assign a resource to the task, which it is willing to share with other
+    /// tasks that also declare it as shared.
+    ///
+    /// Returns the task itself for convenience.
+    pub fn share(self, resource: TaskResource) -> Self {
+        self.declare(resource, AccessMode::Shared)
+    }
+
+    /// Assign a resource to the task that it only ever reads, so that other tasks
+    /// that only read it can hold it too.
+    ///
+    /// Returns the task itself for convenience.
+    pub fn read_only(self, resource: TaskResource) -> Self {
+        self.declare(resource, AccessMode::Input)
+    }
+
+    /// Declare the most permits of the named semaphore that the task may hold at
+    /// once, which the scheduler needs to know in advance to avoid deadlock.
+    ///
+    /// Returns the task itself for convenience.
+    pub fn max_claim(mut self, semaphore: impl Into<String>, permits: usize) -> Self {
+        self.context
+            .max_claims_mut()
+            .insert(semaphore.into(), permits);
+        self
+    }
+
+    /// Set up a resource in the given way whenever the task acquires it, assigning
+    /// it to the task as an output if it hasn't been assigned yet.
+    ///
+    /// Returns the task itself for convenience.
+    pub fn configure(mut self, resource: TaskResource, config: PinConfig) -> Self {
         match resource {
-            TaskResource::Pin(pin) => self.context.pins_used_mut().push(pin),
+            TaskResource::Pin(pin) => {
+                if !self.context.pins_used().contains(&pin) {
+                    self.context.pins_used_mut().push(pin);
+                }
+                if config.is_default() {
+                    self.context.pin_configs_mut().remove(&pin);
+                } else {
+                    self.context.pin_configs_mut().insert(pin, config);
+                }
+            }
         }
 
         self
     }
+
+    /// Assign a resource to the task, held in the given mode.
+    ///
+    /// Returns the task itself for convenience.
+    pub fn declare(mut self, resource: TaskResource, mode: AccessMode) -> Self {
+        match resource {
+            TaskResource::Pin(pin) => {
+                if !self.context.pins_used().contains(&pin) {
+                    self.context.pins_used_mut().push(pin);
+                }
+                match mode {
+                    AccessMode::Output => self.context.pin_modes_mut().remove(&pin),
+                    mode => self.context.pin_modes_mut().insert(pin, mode),
+                };
+            }
+        }
+
+        self
+    }
+}
+
+impl From<Task> for anyhow::Result<Task> {
+    /// Finish building a task, declaring the pins it uses unless that has been turned
+    /// off, and checking that its jumps are in bounds.
+    fn from(mut task: Task) -> Self {
+        if task.infer_pins {
+            task.declare_inferred_pins();
+        }
+        task.check_jumps()?;
+        Ok(task)
+    }
+}
+
+impl fmt::Display for Task {
+    /// Print the task out as the text that it can be assembled from.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&asm::disassemble(self))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{collections::BTreeMap, time};
+
+    use esp_idf_hal::gpio::Level;
+
+    use super::Task;
+    use crate::{
+        resource::{
+            AccessMode, DriveCapability, Edge, PinConfig, PullMode, Request, ResourceManager,
+            WaitMode,
+        },
+        task::{
+            Condition, Operand, Register, RoutineLibrary, Shot, TaskContext, TaskPriority,
+            TaskStep, TimeAnchor, Value,
+        },
+        testing,
+    };
+
+    testing::tests!(
+        infers_inputs,
+        run_finishes_loops,
+        run_stops_endless_loops,
+        sleeps_from_start_of_each_shot
+    );
+
+    /// One of every step, at the limits of what each can hold, naming every
+    /// channel, variable, routine, semaphore and event group `name`.
+    pub fn every_step(name: &str) -> Vec<TaskStep> {
+        let int = Register::Int(7);
+        let bool = Register::Bool(7);
+        vec![
+            TaskStep::ReadGPIO(i32::MIN, bool),
+            TaskStep::WriteGPIO(-1, Level::High),
+            TaskStep::WriteGPIO(i32::MAX, Level::Low),
+            TaskStep::Yield(u32::MAX),
+            TaskStep::YieldNow,
+            TaskStep::SleepUntil(0, TimeAnchor::Start),
+            TaskStep::SleepUntil(u32::MAX, TimeAnchor::Mark),
+            TaskStep::MarkTime,
+            TaskStep::WaitLevel(0, Level::High, None, None),
+            TaskStep::WaitLevel(-2, Level::Low, Some(u32::MAX), Some(bool)),
+            TaskStep::WaitEdge(39, Edge::Rising, Some(0), Some(int)),
+            TaskStep::WaitEdge(1, Edge::Falling, None, None),
+            TaskStep::WaitEdge(2, Edge::Any, Some(u32::MAX - 1), None),
+            TaskStep::Send(name.into(), i32::MIN),
+            TaskStep::Recv(name.into(), int),
+            TaskStep::Store(name.into(), Register::Int(0)),
+            TaskStep::Load(Register::Bool(0), name.into()),
+            TaskStep::Set(int, Operand::Immediate(Value::Int(i32::MAX))),
+            TaskStep::Set(bool, Operand::Immediate(Value::Bool(true))),
+            TaskStep::Add(int, Operand::Register(Register::Int(0))),
+            TaskStep::Sub(int, Operand::Immediate(Value::Int(i32::MIN))),
+            TaskStep::Compare(bool, int, Operand::Immediate(Value::Bool(false))),
+            TaskStep::Jump(0),
+            TaskStep::JumpIf(int, Condition::Equal, 24),
+            TaskStep::JumpIf(int, Condition::NotEqual, 0),
+            TaskStep::JumpIf(int, Condition::Less, 0),
+            TaskStep::JumpIf(int, Condition::LessOrEqual, 0),
+            TaskStep::JumpIf(int, Condition::Greater, 0),
+            TaskStep::JumpIf(int, Condition::GreaterOrEqual, 0),
+            TaskStep::Loop(int, 41),
+            TaskStep::Call(name.into()),
+            TaskStep::Return,
+            TaskStep::ReadGPIOIndirect(int, bool),
+            TaskStep::WriteGPIOIndirect(int, Level::High),
+            TaskStep::YieldIndirect(int),
+            TaskStep::Acquire(name.into()),
+            TaskStep::Release(name.into()),
+            TaskStep::SetBits(name.into(), u32::MAX),
+            TaskStep::ClearBits(name.into(), 0),
+            TaskStep::WaitBits(name.into(), 1, WaitMode::All, true),
+            TaskStep::WaitBits(name.into(), u32::MAX, WaitMode::Any, false),
+        ]
+    }
+
+    /// A task that sets every part of its description, naming it and the
+    /// semaphore it claims `name`.
+    pub fn described_task(name: &str, steps: Vec<TaskStep>) -> anyhow::Result<Task> {
+        let config = PinConfig::builder()
+            .pull(PullMode::UpDown)
+            .open_drain(true)
+            .drive(DriveCapability::I40mA)
+            .initial_level(Level::High)
+            .build();
+        Task::builder()
+            .context(
+                TaskContext::builder()
+                    .name(name)
+                    .priority(TaskPriority::Custom(7))
+                    .pins_used(vec![5, 6])
+                    .pin_modes(BTreeMap::from([
+                        (5, AccessMode::Input),
+                        (6, AccessMode::Shared),
+                    ]))
+                    .pin_configs(BTreeMap::from([(6, config)]))
+                    .max_claims(BTreeMap::from([(name.to_string(), 2), ("pwm".into(), 1)]))
+                    .build(),
+            )
+            .steps(steps)
+            .shots(Shot::Custom(3))
+            .build()
+    }
+
+    /// Check that two tasks describe the same work, leaving out the runtime state
+    /// of their contexts and the order that their pins were declared in.
+    pub fn assert_same(left: &Task, right: &Task) {
+        let describe = |task: &Task| {
+            let context = task.context();
+            let mut pins = context.pins_used().clone();
+            pins.sort();
+            (
+                context.name().clone(),
+                *context.priority(),
+                *task.shots(),
+                task.steps().clone(),
+                pins,
+                context.pin_modes().clone(),
+                context.pin_configs().clone(),
+                context.max_claims().clone(),
+            )
+        };
+        assert_eq!(describe(left), describe(right));
+    }
+
+    fn task(steps: Vec<TaskStep>) -> anyhow::Result<Task> {
+        Task::builder()
+            .shots(Shot::Custom(1))
+            .context(TaskContext::builder().name("Test").build())
+            .steps(steps)
+            .build()
+    }
+
+    fn infers_inputs() -> anyhow::Result<()> {
+        let task = Task::builder()
+            .context(
+                TaskContext::builder()
+                    .name("Test")
+                    .pins_used(vec![5])
+                    .build(),
+            )
+            .steps(vec![
+                TaskStep::ReadGPIO(3, Register::Bool(0)),
+                TaskStep::WaitEdge(4, Edge::Rising, None, None),
+                TaskStep::ReadGPIO(2, Register::Bool(0)),
+                TaskStep::WriteGPIO(2, Level::High),
+                TaskStep::ReadGPIO(5, Register::Bool(0)),
+            ])
+            .shots(Shot::Infinity)
+            .build()?;
+        let context = task.context();
+        assert_eq!(context.pins_used(), &vec![5, 2, 3, 4]);
+        assert_eq!(context.pin_mode(2), AccessMode::Output);
+        assert_eq!(context.pin_mode(3), AccessMode::Input);
+        assert_eq!(context.pin_mode(4), AccessMode::Input);
+        // Pins assigned by hand keep the mode they were given.
+        assert_eq!(context.pin_mode(5), AccessMode::Output);
+
+        // Writing to a pin from a register might write to any of them.
+        let task = Task::new(
+            "Test",
+            TaskPriority::Low,
+            Shot::Infinity,
+            vec![
+                TaskStep::ReadGPIO(3, Register::Bool(0)),
+                TaskStep::WriteGPIOIndirect(Register::Int(0), Level::High),
+            ],
+        );
+        assert_eq!(task.context().pin_mode(3), AccessMode::Output);
+        Ok(())
+    }
+
+    fn run_finishes_loops() -> anyhow::Result<()> {
+        let mut task = task(vec![
+            TaskStep::Set(Register::Int(0), Operand::Immediate(Value::Int(3))),
+            TaskStep::Add(Register::Int(1), Operand::Immediate(Value::Int(1))),
+            TaskStep::Loop(Register::Int(0), 1),
+        ])?;
+
+        task.run(&mut ResourceManager::for_tests(), &RoutineLibrary::new())?;
+        assert!(task.at_end());
+        assert_eq!(
+            task.context().registers().get(Register::Int(1))?,
+            Value::Int(3)
+        );
+        Ok(())
+    }
+
+    fn run_stops_endless_loops() -> anyhow::Result<()> {
+        // Waiting for a register that nothing changes, as a button loop would.
+        let mut task = task(vec![
+            TaskStep::YieldNow,
+            TaskStep::JumpIf(Register::Int(0), Condition::Equal, 0),
+        ])?;
+
+        let result = task.run(&mut ResourceManager::for_tests(), &RoutineLibrary::new());
+        assert!(result.is_err(), "An endless loop should not finish.");
+        assert!(!task.at_end());
+        Ok(())
+    }
+
+    fn sleeps_from_start_of_each_shot() -> anyhow::Result<()> {
+        let mut task = task(vec![TaskStep::SleepUntil(10, TimeAnchor::Start)])?;
+        *task.shots_mut() = Shot::Custom(2);
+        let mut manager = ResourceManager::for_tests();
+        let routines = RoutineLibrary::new();
+
+        let first = time::Instant::now();
+        task.context_mut().set_last_run_timestamp(first);
+        task.context_mut().set_started_timestamp(Some(first));
+        task.step(&mut manager, &routines)?;
+        let deadline = first + time::Duration::from_millis(10);
+        assert_eq!(
+            task.context_mut().block_requests_mut().pop(),
+            Some(Request::SleepUntil(deadline))
+        );
+
+        // The second shot starts once the first one has slept.
+        let second = deadline + time::Duration::from_millis(5);
+        task.context_mut().set_last_run_timestamp(second);
+        task.step(&mut manager, &routines)?;
+        assert_eq!(*task.context().started_timestamp(), Some(second));
+        assert_eq!(
+            task.context_mut().block_requests_mut().pop(),
+            Some(Request::SleepUntil(
+                second + time::Duration::from_millis(10)
+            ))
+        );
+        Ok(())
+    }
 }
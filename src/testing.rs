@@ -0,0 +1,67 @@
+use anyhow::Context;
+
+/// A single test, which fails by returning an error or by panicking.
+pub type Test = (&'static str, fn() -> anyhow::Result<()>);
+
+/// Declare the tests of a module, so that they are run by `cargo test`.
+///
+/// The tests of every module must also be listed in `TESTS` below.
+#[cfg(test)]
+macro_rules! tests {
+    ($($test:ident),* $(,)?) => {
+        pub const TESTS: &[$crate::testing::Test] = &[
+            $((concat!(module_path!(), "::", stringify!($test)), $test)),*
+        ];
+    };
+}
+
+#[cfg(test)]
+pub(crate) use tests;
+
+/// The tests of every module, by the top-level module they are in, which are
+/// only built by `cargo test`.
+#[cfg(test)]
+const TESTS: &[&[&[Test]]] = &[
+    crate::asm::TESTS,
+    crate::binary::TESTS,
+    #[cfg(feature = "serde")]
+    crate::config::TESTS,
+    crate::resource::TESTS,
+    crate::scheduler::TESTS,
+    crate::task::TESTS,
+];
+
+#[cfg(not(test))]
+const TESTS: &[&[&[Test]]] = &[];
+
+/// Run every test, failing if any of them do.
+///
+/// The test harness can't be built for the chip, so it is turned off and
+/// `cargo test` runs the firmware with this in place of the example tasks.
+pub fn run() -> anyhow::Result<()> {
+    let mut failed = vec![];
+    let tests = || TESTS.iter().copied().flatten().copied().flatten();
+    for &(name, test) in tests() {
+        match std::panic::catch_unwind(test) {
+            Ok(Ok(())) => log::info!("Test {name} passed."),
+            Ok(Err(error)) => {
+                log::error!("Test {name} failed: {error:?}");
+                failed.push(name);
+            }
+            Err(_) => {
+                log::error!("Test {name} panicked.");
+                failed.push(name);
+            }
+        }
+    }
+
+    let total = tests().count();
+    failed.is_empty().then_some(()).context(format!(
+        "{} of {total} tests failed: {}",
+        failed.len(),
+        failed.join(", ")
+    ))?;
+
+    log::info!("All {total} tests passed.");
+    Ok(())
+}
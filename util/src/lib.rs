@@ -1,91 +1,91 @@
-// /// Create a PeripheralReference to a pin or resource managed by the Peripherals struct.
-// ///
-// /// This macro is unsafe, without the condition that only one copy of this reference is used
-// /// by the user, as otherwise any drivers created using that pin or resource will not work
-// /// correctly.
-// #[macro_export]
-// macro_rules! pref {
-//     // Take in a list of expressions, which are some kind of peripheral, and
-//     // create an unsafe reference to them.
-//     ($($peripheral:expr),+) => {{
-//             use esp_idf_hal::{peripheral::Peripheral, gpio::IOPin};
-//             let mut peripherals = vec![];
-//             $(
-//                 peripherals.push(unsafe { $peripheral.clone_unchecked() }
-//                     .downgrade()
-//                     .into_ref());
-//             )+
-//             peripherals
-//     }};
-// }
-
-use proc_macro::Span;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{
-    parse::{Parse, ParseStream},
-    parse_macro_input, Ident,
-};
-
-struct PrefMacroInput {
-    _ident: syn::Ident,
-    _colon: syn::Token![:],
-    range: syn::Expr,
-}
+use syn::parse_macro_input;
 
-impl Parse for PrefMacroInput {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let _ident = input.parse()?;
-        let _colon = input.parse()?;
-        let range = input.parse()?;
-
-        Ok(PrefMacroInput {
-            _ident,
-            _colon,
-            range,
-        })
-    }
-}
+mod pref;
+mod task;
 
+/// Create a map from pin numbers to references to the pins of the peripherals.
+///
+/// The pins of the peripherals are given first, followed by the pins to take as
+/// a list of pins and ranges, where ranges include both ends. Pins can be left
+/// out of the list with `except`:
+///
+/// ```ignore
+/// let pins = util::pref!(peripherals.pins, 0..39 except 6..11, 20, 24, 28..31);
+/// ```
+///
+/// Pins that the chip doesn't have fail to compile, as do input-only pins, such
+/// as GPIO 34 to 39 of the ESP32, and pins that are listed twice or left out
+/// without being listed.
+///
+/// This is how the resource manager takes the pins of the chip it was built for,
+/// so anything else should get its pins from the manager instead.
+///
+/// # Safety
+///
+/// The references are created without taking the pins from the peripherals, so
+/// only one set of references may ever be used, as otherwise any drivers created
+/// using those pins will not work correctly.
 #[proc_macro]
 pub fn pref(input: TokenStream) -> TokenStream {
-    let PrefMacroInput { range, .. } = parse_macro_input!(input as PrefMacroInput);
-
-    let (start, end) = if let syn::Expr::Range(ref range_expr) = range {
-        let start = match range_expr.start.as_deref() {
-            Some(syn::Expr::Lit(syn::ExprLit {
-                lit: syn::Lit::Int(ref lit),
-                ..
-            })) => lit.base10_parse::<u32>().unwrap(),
-            _ => panic!("Expected integer range start."),
-        };
-
-        let end = match range_expr.end.as_deref() {
-            Some(syn::Expr::Lit(syn::ExprLit {
-                lit: syn::Lit::Int(ref lit),
-                ..
-            })) => lit.base10_parse::<u32>().unwrap(),
-            _ => panic!("Expected integer range end."),
-        } + 1;
-
-        (start, end)
-    } else {
-        panic!("Expected a range expression like 1..21");
-    };
-
-    let pins = (start..end).map(|n| {
-        let gpio_ident = Ident::new(&format!("gpio{}", n), Span::call_site().into());
-        quote! {
-            unsafe { peripherals.pins.#gpio_ident.clone_unchecked() }.downgrade().into_ref()
-        }
-    });
-
-    let expanded = quote! {{
-        use esp_idf_hal::gpio::IOPin;
-        let mut v = Vec::new();
-        #(v.push(#pins);)*
-        v
-    }};
+    parse_macro_input!(input as pref::PrefInput)
+        .expand()
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    TokenStream::from(expanded)
+/// Build a task from a description of it, checking its steps at compile time.
+///
+/// The steps are written like the task assembly, separated by semicolons, and
+/// every pin they use has to be declared in `pins`, `inputs` or `shared`. Waits
+/// on pins may store the level they end on, as in `wait_level 3 high -> b0`:
+///
+/// ```ignore
+/// let blink = util::task! {
+///     name: "Blink",
+///     priority: Low,
+///     shots: 5,
+///     pins: [4],
+///     steps: { wait 1s; high 4; wait 1s; low 4; }
+/// }?;
+/// ```
+///
+/// This expands to the calls to `Task::builder()`, so it evaluates to the result
+/// of building the task. Undeclared pins, writes to inputs, invalid durations
+/// and unknown instructions fail to compile at the place they were written.
+/// The priority defaults to `Low` and the shots to `Infinity`, the same as in
+/// the task assembly, so a task without `shots` runs until it is stopped.
+///
+/// # Errors
+///
+/// These are checked before anything is expanded, so each of them is the only
+/// error reported. A pin that has not been declared:
+///
+/// ```compile_fail
+/// util::task! { name: "Undeclared", pins: [4], steps: { high 5; } };
+/// ```
+///
+/// A write to a pin that is declared as an input:
+///
+/// ```compile_fail
+/// util::task! { name: "Input", inputs: [4], steps: { high 4; } };
+/// ```
+///
+/// A duration without a unit:
+///
+/// ```compile_fail
+/// util::task! { name: "Unitless", steps: { wait 1000; } };
+/// ```
+///
+/// An instruction that doesn't exist:
+///
+/// ```compile_fail
+/// util::task! { name: "Unknown", pins: [4], steps: { toggle 4; } };
+/// ```
+#[proc_macro]
+pub fn task(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as task::TaskInput)
+        .expand()
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse::{Parse, ParseStream},
+    Expr, LitInt, Token,
+};
+
+syn::custom_keyword!(except);
+
+/// A single pin, or an inclusive range of pins, along with where it was written.
+struct Item {
+    start: u32,
+    end: u32,
+    span: Span,
+}
+
+impl Parse for Item {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let start: LitInt = input.parse()?;
+        let first = start.base10_parse::<u32>()?;
+
+        let last = if input.peek(Token![..=]) {
+            input.parse::<Token![..=]>()?;
+            Some(input.parse::<LitInt>()?)
+        } else if input.peek(Token![..]) {
+            input.parse::<Token![..]>()?;
+            Some(input.parse::<LitInt>()?)
+        } else {
+            None
+        };
+
+        let Some(last) = last else {
+            return Ok(Self {
+                start: first,
+                end: first,
+                span: start.span(),
+            });
+        };
+
+        let end = last.base10_parse::<u32>()?;
+        let span = start.span().join(last.span()).unwrap_or(start.span());
+        if end < first {
+            return Err(syn::Error::new(
+                span,
+                format!("The range {first}..{end} is empty, as it ends before it starts."),
+            ));
+        }
+
+        Ok(Self {
+            start: first,
+            end,
+            span,
+        })
+    }
+}
+
+/// A list of pins and ranges of pins, separated by commas.
+fn items(input: ParseStream) -> syn::Result<Vec<Item>> {
+    let mut items = vec![input.parse()?];
+    while input.peek(Token![,]) {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() || input.peek(except) {
+            break;
+        }
+        items.push(input.parse()?);
+    }
+    Ok(items)
+}
+
+/// The input of `pref!`, which is the pins of the peripherals followed by the
+/// pins to take from them, such as `peripherals.pins, 0..39 except 6..11`.
+pub struct PrefInput {
+    pins: Expr,
+    included: Vec<Item>,
+    excluded: Vec<Item>,
+}
+
+impl Parse for PrefInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let pins: Expr = input.parse()?;
+        if matches!(pins, Expr::Lit(_) | Expr::Range(_)) {
+            return Err(syn::Error::new_spanned(
+                pins,
+                "Expected the pins of the peripherals, such as `peripherals.pins`, before the pins to take.",
+            ));
+        }
+        input.parse::<Token![,]>()?;
+        let included = items(input)?;
+
+        let excluded = if input.peek(except) {
+            input.parse::<except>()?;
+            items(input)?
+        } else {
+            vec![]
+        };
+
+        if !input.is_empty() {
+            return Err(input.error("Expected a pin, a range of pins or 'except'."));
+        }
+
+        Ok(Self {
+            pins,
+            included,
+            excluded,
+        })
+    }
+}
+
+impl PrefInput {
+    /// Every pin that is taken, along with where it was written.
+    ///
+    /// Pins may only be listed once, and every pin that is excluded must have
+    /// been included, so mistakes in the list are caught.
+    fn pins(&self) -> syn::Result<BTreeMap<u32, Span>> {
+        let mut pins = BTreeMap::new();
+        for item in &self.included {
+            for pin in item.start..=item.end {
+                if pins.insert(pin, item.span).is_some() {
+                    return Err(syn::Error::new(
+                        item.span,
+                        format!("Pin {pin} is listed more than once."),
+                    ));
+                }
+            }
+        }
+
+        for item in &self.excluded {
+            for pin in item.start..=item.end {
+                if pins.remove(&pin).is_none() {
+                    return Err(syn::Error::new(
+                        item.span,
+                        format!("Pin {pin} is excluded, but it was never included."),
+                    ));
+                }
+            }
+        }
+
+        Ok(pins)
+    }
+
+    /// Generate a map from each pin number to a reference to the pin.
+    ///
+    /// Each pin is found by its field on the pins of the peripherals, so a pin
+    /// that the chip doesn't have fails to compile at the number it was written.
+    /// The pin must also be able to both read and drive, so an input-only pin,
+    /// such as GPIO 34 to 39 of the ESP32, fails to compile there too.
+    pub fn expand(&self) -> syn::Result<TokenStream> {
+        let pins = &self.pins;
+        let inserts = self.pins()?.into_iter().map(|(pin, span)| {
+            let field = format_ident!("gpio{}", pin, span = span);
+            let number = pin as i32;
+            quote_spanned! {span=>
+                map.insert(
+                    #number,
+                    unsafe { ::esp_idf_hal::gpio::AnyIOPin::new(io_pin(&pins.#field)) }.into_ref(),
+                );
+            }
+        });
+
+        Ok(quote! {{
+            use ::esp_idf_hal::peripheral::Peripheral as _;
+            fn io_pin(pin: &impl ::esp_idf_hal::gpio::IOPin) -> i32 {
+                ::esp_idf_hal::gpio::Pin::pin(pin)
+            }
+            let pins = &#pins;
+            let mut map = ::std::collections::BTreeMap::new();
+            #(#inserts)*
+            map
+        }})
+    }
+}
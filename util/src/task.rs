@@ -0,0 +1,449 @@
+use std::collections::BTreeSet;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::{
+    braced, bracketed,
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, Lit, LitInt, LitStr, Token,
+};
+
+syn::custom_keyword!(after);
+syn::custom_keyword!(timeout);
+
+/// The instructions that a step can be written with.
+const INSTRUCTIONS: &str = "high, low, read, wait, yield, sleep, mark, wait_level or wait_edge";
+
+/// A pin used by a step, along with where it was written.
+struct Pin {
+    number: i32,
+    span: Span,
+}
+
+impl Parse for Pin {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let lit: LitInt = input.parse()?;
+        Ok(Self {
+            number: lit.base10_parse()?,
+            span: lit.span(),
+        })
+    }
+}
+
+/// A duration such as `1000ms` or `1s`, in milliseconds.
+struct Duration(u32);
+
+impl Parse for Duration {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let lit: Lit = input.parse()?;
+        let Lit::Int(int) = &lit else {
+            return Err(syn::Error::new(
+                lit.span(),
+                "Expected a duration such as 1000ms or 1s.",
+            ));
+        };
+
+        let scale = match int.suffix() {
+            "ms" => 1,
+            "s" => 1000,
+            "" => {
+                return Err(syn::Error::new(
+                    int.span(),
+                    format!("The duration '{int}' needs a unit, such as {int}ms or {int}s."),
+                ))
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    int.span(),
+                    format!("Expected a duration such as 1000ms or 1s, but found '{int}'."),
+                ))
+            }
+        };
+
+        int.base10_parse::<u32>()
+            .ok()
+            .and_then(|value| value.checked_mul(scale))
+            .map(Self)
+            .ok_or_else(|| {
+                syn::Error::new(
+                    int.span(),
+                    format!("'{int}' is out of range for a duration."),
+                )
+            })
+    }
+}
+
+/// A register such as `r0` or `b3`.
+fn register(input: ParseStream) -> syn::Result<TokenStream> {
+    let ident: Ident = input.parse()?;
+    let name = ident.to_string();
+    let (kind, index) = name.split_at(1.min(name.len()));
+    let index = index.parse::<u8>().ok().filter(|index| *index < 8);
+    match (kind, index) {
+        ("r", Some(index)) => Ok(quote!(crate::task::Register::Int(#index))),
+        ("b", Some(index)) => Ok(quote!(crate::task::Register::Bool(#index))),
+        _ => Err(syn::Error::new(
+            ident.span(),
+            format!("Expected a register from r0 to r7 or b0 to b7, but found '{ident}'."),
+        )),
+    }
+}
+
+/// A level that is either `high` or `low`.
+fn level(input: ParseStream) -> syn::Result<TokenStream> {
+    let ident: Ident = input.parse()?;
+    match ident.to_string().as_str() {
+        "high" => Ok(quote!(::esp_idf_hal::gpio::Level::High)),
+        "low" => Ok(quote!(::esp_idf_hal::gpio::Level::Low)),
+        _ => Err(syn::Error::new(
+            ident.span(),
+            format!("Expected 'high' or 'low', but found '{ident}'."),
+        )),
+    }
+}
+
+/// An optional register to store the result of a wait in, such as `-> b0`.
+fn optional_destination(input: ParseStream) -> syn::Result<TokenStream> {
+    if !input.peek(Token![->]) {
+        return Ok(quote!(None));
+    }
+
+    input.parse::<Token![->]>()?;
+    let register = register(input)?;
+    Ok(quote!(Some(#register)))
+}
+
+/// An optional timeout, such as `timeout 1s`, at the end of a wait.
+fn optional_timeout(input: ParseStream) -> syn::Result<TokenStream> {
+    if !input.peek(timeout) {
+        return Ok(quote!(None));
+    }
+
+    input.parse::<timeout>()?;
+    let Duration(ms) = input.parse()?;
+    Ok(quote!(Some(#ms)))
+}
+
+/// A single step, along with the pin that it uses, if any.
+struct Step {
+    pin: Option<Pin>,
+    writes: bool,
+    tokens: TokenStream,
+}
+
+impl Parse for Step {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // Keywords such as 'yield' aren't identifiers, so any of them are taken.
+        let instruction = Ident::parse_any(input)?;
+        let span = instruction.span();
+        let step = |pin, writes, tokens| Self {
+            pin,
+            writes,
+            tokens,
+        };
+
+        match instruction.to_string().as_str() {
+            name @ ("high" | "low") => {
+                let pin: Pin = input.parse()?;
+                let number = pin.number;
+                let level = if name == "high" {
+                    quote!(High)
+                } else {
+                    quote!(Low)
+                };
+                let tokens = quote_spanned! {span=>
+                    crate::task::TaskStep::WriteGPIO(#number, ::esp_idf_hal::gpio::Level::#level)
+                };
+                Ok(step(Some(pin), true, tokens))
+            }
+            "read" => {
+                let pin: Pin = input.parse()?;
+                input.parse::<Token![->]>()?;
+                let register = register(input)?;
+                let number = pin.number;
+                let tokens = quote_spanned! {span=>
+                    crate::task::TaskStep::ReadGPIO(#number, #register)
+                };
+                Ok(step(Some(pin), false, tokens))
+            }
+            "wait" => {
+                let Duration(ms) = input.parse()?;
+                let tokens = quote_spanned! {span=> crate::task::TaskStep::Yield(#ms)};
+                Ok(step(None, false, tokens))
+            }
+            "yield" => {
+                let tokens = quote_spanned! {span=> crate::task::TaskStep::YieldNow};
+                Ok(step(None, false, tokens))
+            }
+            "mark" => {
+                let tokens = quote_spanned! {span=> crate::task::TaskStep::MarkTime};
+                Ok(step(None, false, tokens))
+            }
+            "sleep" => {
+                let Duration(ms) = input.parse()?;
+                input.parse::<after>()?;
+                let anchor: Ident = input.parse()?;
+                let anchor = match anchor.to_string().as_str() {
+                    "start" => quote!(Start),
+                    "mark" => quote!(Mark),
+                    _ => {
+                        return Err(syn::Error::new(
+                            anchor.span(),
+                            format!("Expected 'start' or 'mark', but found '{anchor}'."),
+                        ))
+                    }
+                };
+                let tokens = quote_spanned! {span=>
+                    crate::task::TaskStep::SleepUntil(#ms, crate::task::TimeAnchor::#anchor)
+                };
+                Ok(step(None, false, tokens))
+            }
+            "wait_level" => {
+                let pin: Pin = input.parse()?;
+                let level = level(input)?;
+                let timeout = optional_timeout(input)?;
+                let destination = optional_destination(input)?;
+                let number = pin.number;
+                let tokens = quote_spanned! {span=>
+                    crate::task::TaskStep::WaitLevel(#number, #level, #timeout, #destination)
+                };
+                Ok(step(Some(pin), false, tokens))
+            }
+            "wait_edge" => {
+                let pin: Pin = input.parse()?;
+                let edge: Ident = input.parse()?;
+                let edge = match edge.to_string().as_str() {
+                    "rising" => quote!(Rising),
+                    "falling" => quote!(Falling),
+                    "any" => quote!(Any),
+                    _ => {
+                        return Err(syn::Error::new(
+                            edge.span(),
+                            format!("Expected 'rising', 'falling' or 'any', but found '{edge}'."),
+                        ))
+                    }
+                };
+                let timeout = optional_timeout(input)?;
+                let destination = optional_destination(input)?;
+                let number = pin.number;
+                let tokens = quote_spanned! {span=>
+                    crate::task::TaskStep::WaitEdge(
+                        #number,
+                        crate::resource::Edge::#edge,
+                        #timeout,
+                        #destination
+                    )
+                };
+                Ok(step(Some(pin), false, tokens))
+            }
+            _ => Err(syn::Error::new(
+                span,
+                format!("Unknown instruction '{instruction}', expected one of {INSTRUCTIONS}."),
+            )),
+        }
+    }
+}
+
+/// The input of `task!`, which is a list of fields describing the task.
+pub struct TaskInput {
+    name: Option<LitStr>,
+    priority: Option<TokenStream>,
+    shots: Option<TokenStream>,
+    pins: Vec<Pin>,
+    inputs: Vec<Pin>,
+    shared: Vec<Pin>,
+    steps: Vec<Step>,
+}
+
+impl Parse for TaskInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut task = Self {
+            name: None,
+            priority: None,
+            shots: None,
+            pins: vec![],
+            inputs: vec![],
+            shared: vec![],
+            steps: vec![],
+        };
+        let mut seen = BTreeSet::new();
+
+        while !input.is_empty() {
+            let field: Ident = input.parse()?;
+            input.parse::<Token![:]>()?;
+            if !seen.insert(field.to_string()) {
+                return Err(syn::Error::new(
+                    field.span(),
+                    format!("The field '{field}' is given more than once."),
+                ));
+            }
+
+            match field.to_string().as_str() {
+                "name" => task.name = Some(input.parse()?),
+                "priority" => task.priority = Some(priority(input)?),
+                "shots" => task.shots = Some(shots(input)?),
+                "pins" => task.pins = pins(input)?,
+                "inputs" => task.inputs = pins(input)?,
+                "shared" => task.shared = pins(input)?,
+                "steps" => {
+                    let content;
+                    braced!(content in input);
+                    while !content.is_empty() {
+                        task.steps.push(content.parse()?);
+                        if !content.is_empty() {
+                            content.parse::<Token![;]>()?;
+                        }
+                    }
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        field.span(),
+                        format!(
+                            "Unknown field '{field}', expected one of name, priority, shots, pins, inputs, shared or steps."
+                        ),
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(task)
+    }
+}
+
+/// A priority, which is either `Low`, `Normal`, `High` or a number up to 255.
+fn priority(input: ParseStream) -> syn::Result<TokenStream> {
+    if input.peek(LitInt) {
+        let value = input.parse::<LitInt>()?.base10_parse::<u8>()?;
+        return Ok(quote!(crate::task::TaskPriority::from(#value)));
+    }
+
+    let ident: Ident = input.parse()?;
+    match ident.to_string().as_str() {
+        "Low" | "Normal" | "High" => Ok(quote!(crate::task::TaskPriority::#ident)),
+        _ => Err(syn::Error::new(
+            ident.span(),
+            format!("Expected Low, Normal, High or a number, but found '{ident}'."),
+        )),
+    }
+}
+
+/// The number of shots, which is either a number or `Infinity`.
+fn shots(input: ParseStream) -> syn::Result<TokenStream> {
+    if input.peek(LitInt) {
+        let value = input.parse::<LitInt>()?.base10_parse::<usize>()?;
+        return Ok(quote!(crate::task::Shot::Custom(#value)));
+    }
+
+    let ident: Ident = input.parse()?;
+    if ident != "Infinity" {
+        return Err(syn::Error::new(
+            ident.span(),
+            format!("Expected Infinity or a number, but found '{ident}'."),
+        ));
+    }
+    Ok(quote!(crate::task::Shot::Infinity))
+}
+
+/// A list of pins such as `[2, 4]`.
+fn pins(input: ParseStream) -> syn::Result<Vec<Pin>> {
+    let content;
+    bracketed!(content in input);
+    Ok(Punctuated::<Pin, Token![,]>::parse_terminated(&content)?
+        .into_iter()
+        .collect())
+}
+
+impl TaskInput {
+    /// Make sure every pin is declared once, and that every step only uses the
+    /// pins that have been declared in a way that they can be used.
+    fn check(&self) -> syn::Result<()> {
+        let mut declared = BTreeSet::new();
+        for pin in self.pins.iter().chain(&self.inputs).chain(&self.shared) {
+            if !declared.insert(pin.number) {
+                return Err(syn::Error::new(
+                    pin.span,
+                    format!("Pin {} is declared more than once.", pin.number),
+                ));
+            }
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            let Some(pin) = &step.pin else {
+                continue;
+            };
+
+            if !declared.contains(&pin.number) {
+                return Err(syn::Error::new(
+                    pin.span,
+                    format!(
+                        "Step {index} uses pin {}, which has not been declared.",
+                        pin.number
+                    ),
+                ));
+            }
+            if step.writes && self.inputs.iter().any(|input| input.number == pin.number) {
+                return Err(syn::Error::new(
+                    pin.span,
+                    format!(
+                        "Step {index} drives pin {}, which is declared as an input.",
+                        pin.number
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generate the calls to the builder of the task, which evaluate to the
+    /// result of building it.
+    pub fn expand(&self) -> syn::Result<TokenStream> {
+        let name = self.name.as_ref().ok_or_else(|| {
+            syn::Error::new(
+                Span::call_site(),
+                "The task needs a name, such as `name: \"Blink\"`.",
+            )
+        })?;
+        self.check()?;
+
+        let priority = self
+            .priority
+            .clone()
+            .unwrap_or_else(|| quote!(crate::task::TaskPriority::Low));
+        let shots = self
+            .shots
+            .clone()
+            .unwrap_or_else(|| quote!(crate::task::Shot::Infinity));
+        let numbers = |pins: &[Pin]| pins.iter().map(|pin| pin.number).collect::<Vec<_>>();
+        let (pins, inputs, shared) = (
+            numbers(&self.pins),
+            numbers(&self.inputs),
+            numbers(&self.shared),
+        );
+        let steps = self.steps.iter().map(|step| &step.tokens);
+
+        Ok(quote! {
+            crate::task::Task::builder()
+                .shots(#shots)
+                .context(
+                    crate::task::TaskContext::builder()
+                        .name(#name)
+                        .priority(#priority)
+                        .pins_used(vec![#(#pins,)* #(#inputs,)* #(#shared,)*])
+                        .pin_modes(::std::collections::BTreeMap::from([
+                            #((#inputs, crate::resource::AccessMode::Input),)*
+                            #((#shared, crate::resource::AccessMode::Shared),)*
+                        ]))
+                        .build(),
+                )
+                .steps(vec![#(#steps),*])
+                .build()
+        })
+    }
+}